@@ -2,4 +2,15 @@ fn main() {
     //link library 
     println!("cargo:rustc-link-search=rarezip/gzip", );
     println!("cargo:rustc-link-lib=rarezip", );
-}
\ No newline at end of file
+
+    //rarezip is a submodule without a crates.io release, its manifest version goes into
+    //reports and cache keys so output from a different codec is never mistaken for ours
+    let rarezip_version = std::fs::read_to_string("rarezip/rust/Cargo.toml").ok()
+        .and_then(|manifest| manifest.lines().find_map(|line| {
+            let value = line.strip_prefix("version")?.trim_start().strip_prefix('=')?;
+            Some(value.trim().trim_matches('"').to_string())
+        }))
+        .unwrap_or_else(|| String::from("unknown"));
+    println!("cargo:rustc-env=RAREZIP_VERSION={}", rarezip_version);
+    println!("cargo:rerun-if-changed=rarezip/rust/Cargo.toml");
+}
@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+use crate::comp;
+use bk_rom_comp::{profile, slice_overlays, BkRomError, Overlay, Profile};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// elf the uncompressed rom was built from
+    #[arg(value_name = "ELF")]
+    elf_path: String,
+
+    /// uncompressed rom in any byte order, or a directory of loose overlay binaries
+    /// from decompress --split
+    #[arg(value_name = "UNCOMPRESSED_ROM")]
+    uncomp_rom_path: String,
+
+    /// sets version of banjo used [default: us.v10]
+    #[arg(short = 'v', long = "game-version", value_name = "VERSION_ID", value_parser = comp::version_names())]
+    game_version: Option<String>,
+
+    /// reads the version's overlays from a TOML game definition instead of the built in ones
+    #[arg(long, value_name = "FILE", conflicts_with = "game_version")]
+    game_def: Option<String>,
+}
+
+//order-0 shannon entropy in bits per byte
+fn entropy(bytes : &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    bytes.iter().for_each(|b| counts[*b as usize] += 1);
+    counts.iter().filter(|c| **c != 0).fold(0.0, |acc, c| {
+        let p = *c as f64 / bytes.len() as f64;
+        acc - p * p.log2()
+    })
+}
+
+//rough compressed size estimate (entropy coded payload + rarezip header)
+fn estimate_rzip_len(bytes : &[u8]) -> usize {
+    6 + (bytes.len() as f64 * entropy(bytes) / 8.0).ceil() as usize
+}
+
+fn print_analysis(overlay_names: &[&str], code_bytes: &[Vec<u8>], data_bytes: &[Vec<u8>]){
+    println!("{:<10} {:>9} {:>7} {:>9} {:>9} {:>7} {:>9} {:>10}", "overlay", "code", "entropy", "est", "data", "entropy", "est", "est total");
+    let mut total = 0;
+    for ((name, code), data) in overlay_names.iter().zip(code_bytes).zip(data_bytes){
+        let code_est = estimate_rzip_len(code);
+        let data_est = estimate_rzip_len(data);
+        let ovrly_est = (code_est + data_est + (16-1)) & !(16-1);
+        total += ovrly_est;
+        println!("{:<10} {:>#9X} {:>7.3} {:>#9X} {:>#9X} {:>7.3} {:>#9X} {:>#10X}", name, code.len(), entropy(code), code_est, data.len(), entropy(data), data_est, ovrly_est);
+    }
+    println!("estimated total: {:#X}", total);
+}
+
+//estimates without running the encoder, so it's quick enough to check whether a change will fit
+pub fn run(args: Args) -> Result<(), BkRomError> {
+    let profile : Cow<Profile> = match &args.game_def {
+        Some(path) => Cow::Owned(profile::load(path)?),
+        None => Cow::Borrowed(profile::builtin(&comp::game_id(args.game_version.as_deref()))),
+    };
+    let symbols = bk_rom_comp::read_elf_symbols(&args.elf_path)?;
+    let overlay_names = profile.overlay_names();
+    let overlay_offsets = overlay_names.iter().map(|name| Overlay::from_elf_symbols(name, &symbols)).collect::<Result<Vec<Overlay>, _>>()?;
+
+    let mut uncompressed_map = None;
+    let uncompressed_rom = comp::read_uncompressed_rom(&args.uncomp_rom_path, &overlay_offsets, &mut uncompressed_map)?;
+    let (code_bytes, data_bytes) = slice_overlays(&overlay_offsets, &uncompressed_rom);
+    print_analysis(&overlay_names, &code_bytes, &data_bytes);
+    Ok(())
+}
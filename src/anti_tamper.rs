@@ -0,0 +1,57 @@
+use crate::BkRomError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    Code,
+    Data,
+}
+
+//an anti-tamper slot, a word in overlay's data that the game compares against
+//one half (word 0 or 1) of the bk_crc of source's code or data
+#[derive(Debug, Clone)]
+pub struct CrcSlot {
+    pub symbol: String,
+    pub overlay: String,
+    pub source: String,
+    pub section: Section,
+    pub word: usize,
+}
+
+//reads a descriptor supplied by the user, e.g. for a hack that moved the slots
+pub fn load(path: &str) -> Result<Vec<CrcSlot>, BkRomError> {
+    let descriptor = std::fs::read_to_string(path).map_err(BkRomError::file(path))?;
+    parse(&descriptor).map_err(|e| BkRomError::BadSymbol(format!("\"{}\": {}", path, e)))
+}
+
+//slot = [{ symbol = "D_8038AAE0", overlay = "SM", source = "SM", section = "code", word = 0 }, ...]
+pub fn parse(descriptor: &str) -> Result<Vec<CrcSlot>, String> {
+    let table : toml::Table = descriptor.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+    from_table(&table)
+}
+
+//the slot array of a descriptor or game definition, the built in slots live in profile/*.toml
+pub fn from_table(table: &toml::Table) -> Result<Vec<CrcSlot>, String> {
+    let slots = table.get("slot").and_then(|s| s.as_array()).ok_or("expected a slot array")?;
+    //the game checks its crcs whether or not any slots are listed
+    if slots.is_empty() {
+        return Err(String::from("the slot array is empty, the rom would fail every anti-tamper check"));
+    }
+    slots.iter().enumerate().map(|(i, slot)| {
+        let field = |name: &str| slot.get(name).ok_or(format!("slot {} has no {}", i, name));
+        let string = |name: &str| field(name)?.as_str().map(str::to_string).ok_or(format!("slot {} {} is not a string", i, name));
+        Ok(CrcSlot {
+            symbol: string("symbol")?,
+            overlay: string("overlay")?,
+            source: string("source")?,
+            section: match string("section")?.as_str() {
+                "code" => Section::Code,
+                "data" => Section::Data,
+                s => return Err(format!("slot {} section \"{}\" is not code or data", i, s)),
+            },
+            word: match field("word")?.as_integer() {
+                Some(w @ (0 | 1)) => w as usize,
+                _ => return Err(format!("slot {} word is not 0 or 1", i)),
+            },
+        })
+    }).collect()
+}
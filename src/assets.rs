@@ -0,0 +1,333 @@
+use std::fmt::Write;
+use std::fs;
+use crate::texture::{self, TextureFormat};
+use bk_rom_comp::{as_big_endian, cic, map_rom, offsets, profile, rom_from_big_endian, rom_to_big_endian, rzip_uncompressed_size, BkRomError, ByteOrder, GameId, GameVersion};
+
+pub const AFTER_HELP : &str = concat!(
+    "the table is a count and a reserved word followed by 8 byte entries (data offset,\n",
+    "compressed flag, type flags), its last entry marks the end of the data, it sits right\n",
+    "before the overlays and is found from there unless --offset is given\n",
+    "extract writes every asset (decompressed) as <id>.bin plus an assets.toml index,\n",
+    "pack reads them back, recompresses the compressed ones and rewrites the table in place");
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// writes every asset of a compressed rom into a directory
+    Extract {
+        /// compressed rom, in any byte order
+        #[arg(value_name = "ROM")]
+        rom: String,
+
+        /// directory the assets and assets.toml are written to
+        #[arg(value_name = "DIR")]
+        dir: String,
+
+        /// rom offset of the asset table (hex with 0x or decimal), by default the
+        /// version's known offset or the table whose data ends at the first overlay
+        #[arg(long, value_name = "OFFSET", value_parser = parse_offset)]
+        offset: Option<usize>,
+    },
+    /// rebuilds the asset table of a rom from an extracted directory, fails if the
+    /// assets no longer fit the space the original table took up
+    Pack {
+        /// directory with assets.toml, as written by extract
+        #[arg(value_name = "DIR")]
+        dir: String,
+
+        /// compressed rom rewritten in place, its byte order is kept
+        #[arg(value_name = "ROM")]
+        rom: String,
+    },
+    /// decodes a texture inside an extracted asset to a png
+    Texture {
+        /// extracted asset, e.g. 0A1F.bin
+        #[arg(value_name = "FILE")]
+        file: String,
+
+        /// png to write
+        #[arg(value_name = "OUT_PNG")]
+        output: String,
+
+        #[arg(long, value_enum)]
+        format: TextureFormat,
+
+        /// width and height in pixels, e.g. 32x32
+        #[arg(long, value_name = "WxH", value_parser = parse_size)]
+        size: (usize, usize),
+
+        /// offset of the pixels in the file
+        #[arg(long, value_name = "OFFSET", value_parser = parse_offset, default_value = "0")]
+        offset: usize,
+
+        /// offset of the rgba16 palette in the file, needed for ci4 and ci8
+        #[arg(long, value_name = "OFFSET", value_parser = parse_offset)]
+        palette_offset: Option<usize>,
+    },
+    /// encodes a png back into a texture inside an extracted asset, in place
+    ImportTexture {
+        /// png to encode, its size has to match --size
+        #[arg(value_name = "PNG")]
+        png: String,
+
+        /// extracted asset the texture is written into, e.g. 0A1F.bin
+        #[arg(value_name = "FILE")]
+        file: String,
+
+        #[arg(long, value_enum)]
+        format: TextureFormat,
+
+        /// width and height in pixels of the texture being replaced, e.g. 32x32
+        #[arg(long, value_name = "WxH", value_parser = parse_size)]
+        size: (usize, usize),
+
+        /// offset of the pixels in the file
+        #[arg(long, value_name = "OFFSET", value_parser = parse_offset, default_value = "0")]
+        offset: usize,
+
+        /// offset of the rgba16 palette in the file, needed for ci4 and ci8
+        #[arg(long, value_name = "OFFSET", value_parser = parse_offset)]
+        palette_offset: Option<usize>,
+    },
+}
+
+fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    size.split_once(['x', 'X']).and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+        .filter(|&(w, h)| w > 0 && h > 0)
+        .ok_or(format!("\"{}\" is not a size, expected e.g. 32x64", size))
+}
+
+fn parse_offset(offset: &str) -> Result<usize, String> {
+    match offset.strip_prefix("0x").or(offset.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => offset.parse(),
+    }.map_err(|_| format!("\"{}\" is not an offset, expected e.g. 0x10CD0", offset))
+}
+
+pub struct Asset {
+    pub offset: usize,
+    pub compressed: bool,
+    pub flags: u16,
+}
+
+pub struct AssetTable {
+    pub reserved: u32,
+    //includes the closing entry that marks the end of the data
+    pub entries: Vec<Asset>,
+    //rom offset the entry offsets are relative to
+    pub data_start: usize,
+}
+
+impl AssetTable {
+    pub fn parse(rom: &[u8], offset: usize) -> Result<Self, String> {
+        let word = |at: usize| rom.get(at .. at + 4).map(|w| u32::from_be_bytes(w.try_into().unwrap())).ok_or(format!("asset table runs past the end of the rom at 0x{:X}", at));
+        let count = word(offset)? as usize;
+        let reserved = word(offset + 4)?;
+        let data_start = offset + 8 + 8*count;
+        if count == 0 || data_start > rom.len() {
+            return Err(format!("0x{:X} does not hold an asset table ({} entries)", offset, count));
+        }
+        let entries : Vec<Asset> = (0..count).map(|i| {
+            let entry = offset + 8 + 8*i;
+            Ok(Asset {
+                offset: word(entry)? as usize,
+                compressed: rom[entry + 4 .. entry + 6] != [0, 0],
+                flags: u16::from_be_bytes([rom[entry + 6], rom[entry + 7]]),
+            })
+        }).collect::<Result<_, String>>()?;
+        if let Some(i) = (1..count).find(|&i| entries[i].offset < entries[i - 1].offset) {
+            return Err(format!("asset {} starts before asset {}, 0x{:X} is probably not an asset table", i, i - 1, offset));
+        }
+        if data_start + entries[count - 1].offset > rom.len() {
+            return Err(format!("asset data ends past the end of the rom (0x{:X})", data_start + entries[count - 1].offset));
+        }
+        Ok(AssetTable{reserved, entries, data_start})
+    }
+
+    //rom bytes of each asset, empty for unused ids
+    pub fn slices<'a>(&self, rom: &'a [u8]) -> Vec<&'a [u8]> {
+        self.entries.windows(2).map(|w| &rom[self.data_start + w[0].offset .. self.data_start + w[1].offset]).collect()
+    }
+
+    pub fn end(&self) -> usize {
+        self.data_start + self.entries.last().map_or(0, |e| e.offset)
+    }
+}
+
+//the game definition's offset when the table is there, otherwise the assets sit right
+//before the overlays, so the table is the one whose data ends at the first overlay
+fn find_table(rom: &[u8]) -> Result<usize, String> {
+    let game_id = GameId::from_header(rom).unwrap_or(GameId::BanjoKazooie(GameVersion::USA));
+    let profile = profile::builtin(&game_id);
+    if let Some(offset) = profile.asset_table.filter(|offset| AssetTable::parse(rom, *offset).is_ok()) {
+        return Ok(offset);
+    }
+    let first_overlay = offsets::discover(rom, profile).and_then(|o| o.file_offsets.first().copied())
+        .ok_or("no overlays found to locate the asset table from, pass --offset")?;
+    (0x1000..first_overlay).step_by(8)
+        .find(|offset| AssetTable::parse(rom, *offset).is_ok_and(|table| table.end() <= first_overlay && first_overlay - table.end() < 0x10))
+        .ok_or(format!("no asset table ends at the first overlay (0x{:X}), pass --offset", first_overlay))
+}
+
+pub fn run(args: Args) -> Result<(), BkRomError> {
+    match args.command {
+        Command::Extract{rom, dir, offset} => extract(&rom, &dir, offset),
+        Command::Pack{dir, rom} => pack(&dir, &rom),
+        Command::Texture{file, output, format, size: (width, height), offset, palette_offset} => {
+            let bytes = fs::read(&file).map_err(BkRomError::file(&file))?;
+            let palette = match (format.palette_size(), palette_offset) {
+                (0, _) => &[][..],
+                (_, Some(palette_offset)) => bytes.get(palette_offset..).unwrap_or_default(),
+                (_, None) => return Err(BkRomError::BadRom(format!("{} textures need --palette-offset", format.name()))),
+            };
+            let rgba = texture::decode(format, bytes.get(offset..).unwrap_or_default(), width, height, palette)
+                .map_err(|e| BkRomError::BadRom(format!("\"{}\": {}", file, e)))?;
+            fs::write(&output, texture::to_png(&rgba, width, height)).map_err(BkRomError::file(&output))
+        },
+        Command::ImportTexture{png, file, format, size, offset, palette_offset} => import_texture(&png, &file, format, size, offset, palette_offset),
+    }
+}
+
+fn extract(rom_path: &str, dir: &str, offset: Option<usize>) -> Result<(), BkRomError> {
+    let source = map_rom(rom_path)?;
+    let rom = as_big_endian(&source)?;
+    let offset = match offset {
+        Some(offset) => offset,
+        None => find_table(&rom).map_err(|e| BkRomError::BadRom(format!("\"{}\": {}", rom_path, e)))?,
+    };
+    let table = AssetTable::parse(&rom, offset).map_err(|e| BkRomError::BadRom(format!("\"{}\": {}", rom_path, e)))?;
+    fs::create_dir_all(dir).map_err(BkRomError::file(dir))?;
+
+    let closing = table.entries.last().unwrap();
+    let mut index = format!(concat!("# written by bkrom assets extract from {}\n",
+        "table_offset = 0x{:X}\ntable_end = 0x{:X}\nreserved = 0x{:X}\n",
+        "# the entry closing the table\nend_compressed = {}\nend_flags = 0x{:04X}\n"),
+        rom_path, offset, table.end(), table.reserved, closing.compressed, closing.flags);
+    for (id, (entry, bytes)) in table.entries.iter().zip(table.slices(&rom)).enumerate() {
+        write!(index, "\n[[asset]]\nid = 0x{:04X}\ncompressed = {}\nflags = 0x{:04X}\n", id, entry.compressed, entry.flags).unwrap();
+        if bytes.is_empty() {
+            continue;
+        }
+        let contents = match entry.compressed {
+            true => {
+                let size = rzip_uncompressed_size(bytes).map_err(|_| BkRomError::BadRom(format!("asset 0x{:04X} is flagged compressed but has no rarezip header", id)))?;
+                let unzipped = rarezip::bk::unzip(bytes);
+                if unzipped.len() != size {
+                    return Err(BkRomError::BadRom(format!("asset 0x{:04X} decompressed to 0x{:X} bytes, its header says 0x{:X}", id, unzipped.len(), size)));
+                }
+                unzipped
+            },
+            false => bytes.to_vec(),
+        };
+        let file = format!("{:04X}.bin", id);
+        fs::write(std::path::Path::new(dir).join(&file), &contents)?;
+        writeln!(index, "file = \"{}\"", file).unwrap();
+    }
+    let index_path = std::path::Path::new(dir).join("assets.toml");
+    fs::write(&index_path, index).map_err(BkRomError::file(&index_path))?;
+    log::info!("{}: {} assets from 0x{:X} to 0x{:X}", dir, table.entries.len() - 1, offset, table.end());
+    Ok(())
+}
+
+//table and data as they are laid out in the rom, each asset 8 byte aligned
+fn build_table(dir: &str, index: &toml::Table) -> Result<Vec<u8>, String> {
+    let int = |table: &toml::Table, key: &str| table.get(key).and_then(|v| v.as_integer()).ok_or(format!("expected an integer {}", key));
+    let flag = |table: &toml::Table, key: &str| table.get(key).and_then(|v| v.as_bool()).ok_or(format!("expected {} = true or false", key));
+    let assets = index.get("asset").and_then(|a| a.as_array()).ok_or("expected an asset array")?;
+
+    let mut entries : Vec<(bool, u16)> = Vec::new();
+    let mut data : Vec<Vec<u8>> = Vec::new();
+    for (i, asset) in assets.iter().enumerate() {
+        let asset = asset.as_table().ok_or(format!("asset {} is not a table", i))?;
+        if int(asset, "id")? != i as i64 {
+            return Err(format!("asset {} has id {}, ids have to count up from 0", i, int(asset, "id")?));
+        }
+        let compressed = flag(asset, "compressed")?;
+        let bytes = match asset.get("file").and_then(|f| f.as_str()) {
+            Some(file) => {
+                let path = std::path::Path::new(dir).join(file);
+                let contents = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                if compressed {rarezip::bk::zip(&contents)} else {contents}
+            },
+            None => Vec::new(),
+        };
+        entries.push((compressed, int(asset, "flags")? as u16));
+        data.push(bytes);
+    }
+    entries.push((flag(index, "end_compressed")?, int(index, "end_flags")? as u16));
+
+    let mut packed : Vec<u8> = Vec::new();
+    packed.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+    packed.extend_from_slice(&(int(index, "reserved")? as u32).to_be_bytes());
+    let mut offset = 0;
+    for (i, (compressed, flags)) in entries.iter().enumerate() {
+        packed.extend_from_slice(&(offset as u32).to_be_bytes());
+        packed.extend_from_slice(&(*compressed as u16).to_be_bytes());
+        packed.extend_from_slice(&flags.to_be_bytes());
+        offset += data.get(i).map_or(0, |d| d.len().next_multiple_of(8));
+    }
+    for mut bytes in data {
+        bytes.resize(bytes.len().next_multiple_of(8), 0);
+        packed.extend_from_slice(&bytes);
+    }
+    Ok(packed)
+}
+
+fn pack(dir: &str, rom_path: &str) -> Result<(), BkRomError> {
+    let index_path = std::path::Path::new(dir).join("assets.toml");
+    let index : toml::Table = fs::read_to_string(&index_path).map_err(BkRomError::file(&index_path))?
+        .parse().map_err(|e: toml::de::Error| BkRomError::BadRom(format!("{}: {}", index_path.display(), e.message())))?;
+    let bad_index = |e: String| BkRomError::BadRom(format!("{}: {}", index_path.display(), e));
+    let bound = |key: &str| index.get(key).and_then(|v| v.as_integer()).map(|v| v as usize).ok_or(format!("expected an integer {}", key));
+    let (table_offset, table_end) = (bound("table_offset").map_err(bad_index)?, bound("table_end").map_err(bad_index)?);
+    let packed = build_table(dir, &index).map_err(bad_index)?;
+    if table_offset + packed.len() > table_end {
+        return Err(BkRomError::BadRom(format!("packed assets take 0x{:X} bytes, only 0x{:X} are available between 0x{:X} and 0x{:X} (raise table_end in assets.toml if the space after it is unused)",
+            packed.len(), table_end - table_offset, table_offset, table_end)));
+    }
+
+    let original = fs::read(rom_path).map_err(BkRomError::file(rom_path))?;
+    let order = ByteOrder::detect(&original).ok_or(BkRomError::BadRom(format!("\"{}\" is not an n64 rom (unknown byte order signature)", rom_path)))?;
+    let mut rom = rom_to_big_endian(original)?;
+    if table_end > rom.len() {
+        return Err(BkRomError::BadRom(format!("\"{}\" ends before the asset table (0x{:X})", rom_path, table_end)));
+    }
+    rom[table_offset .. table_offset + packed.len()].copy_from_slice(&packed);
+    rom[table_offset + packed.len() .. table_end].fill(0);
+    cic::fix_crc(&mut rom).ok_or(BkRomError::BadRom(String::from("unknown cic bootcode, can't calculate the header crc")))?;
+    rom_from_big_endian(&mut rom, order);
+    fs::write(rom_path, &rom).map_err(BkRomError::file(rom_path))?;
+    log::info!("{}: packed 0x{:X} of 0x{:X} bytes of assets at 0x{:X}", rom_path, packed.len(), table_end - table_offset, table_offset);
+    Ok(())
+}
+
+fn import_texture(png_path: &str, file: &str, format: TextureFormat, size: (usize, usize), offset: usize, palette_offset: Option<usize>) -> Result<(), BkRomError> {
+    let png = fs::read(png_path).map_err(BkRomError::file(png_path))?;
+    let bad_png = |e: String| BkRomError::BadRom(format!("\"{}\": {}", png_path, e));
+    let (rgba, width, height) = texture::from_png(&png).map_err(bad_png)?;
+    if (width, height) != size {
+        return Err(bad_png(format!("the image is {}x{}, the texture it replaces is {}x{}", width, height, size.0, size.1)));
+    }
+    let (pixels, palette) = texture::encode(format, &rgba).map_err(bad_png)?;
+
+    let mut bytes = fs::read(file).map_err(BkRomError::file(file))?;
+    let mut place = |data: &[u8], at: usize, what: &str| match bytes.get_mut(at .. at + data.len()) {
+        Some(target) => {
+            target.copy_from_slice(data);
+            Ok(())
+        },
+        None => Err(BkRomError::BadRom(format!("\"{}\" is 0x{:X} bytes, too short for the {} at 0x{:X}", file, bytes.len(), what, at))),
+    };
+    place(&pixels, offset, "pixels")?;
+    match (palette.is_empty(), palette_offset) {
+        (true, _) => {},
+        (false, Some(palette_offset)) => place(&palette, palette_offset, "palette")?,
+        (false, None) => return Err(BkRomError::BadRom(format!("{} textures need --palette-offset", format.name()))),
+    }
+    fs::write(file, &bytes).map_err(BkRomError::file(file))
+}
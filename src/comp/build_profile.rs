@@ -0,0 +1,77 @@
+use bk_rom_comp::BkRomError;
+
+//options a --profile turns on, see build_profiles.toml
+#[derive(Debug, Default, PartialEq)]
+pub struct BuildProfile {
+    pub cache: bool,
+    pub cache_dir: Option<String>,
+    pub self_check: bool,
+    pub verify: bool,
+    pub require_match: bool,
+    pub antitamper_config: Option<String>,
+}
+
+//looked up in the working directory when --profile-config isn't given
+const PROJECT_CONFIG : &str = "bkrom.toml";
+
+//the named profile from the project config, falling back to the built in ones
+pub fn load(name: &str, config_path: Option<&str>) -> Result<BuildProfile, BkRomError> {
+    let path = config_path.or(std::path::Path::new(PROJECT_CONFIG).exists().then_some(PROJECT_CONFIG));
+    if let Some(path) = path {
+        let config = std::fs::read_to_string(path).map_err(BkRomError::file(path))?;
+        if let Some(profile) = find(&config, name).map_err(|e| BkRomError::BadSymbol(format!("\"{}\": {}", path, e)))? {
+            log::info!("profile {} from {}", name, path);
+            return Ok(profile);
+        }
+    }
+    match find(include_str!("build_profiles.toml"), name).unwrap() {
+        Some(profile) => Ok(profile),
+        None => crate::usage_error("compress", clap::error::ErrorKind::InvalidValue,
+            &format!("unknown profile \"{}\", expected dev, release, matching or a [profile.{}] table in {}", name, name, path.unwrap_or(PROJECT_CONFIG))),
+    }
+}
+
+//[profile.<name>] of a config, None if it doesn't define one
+fn find(config: &str, name: &str) -> Result<Option<BuildProfile>, String> {
+    let table : toml::Table = config.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+    let Some(profile) = table.get("profile").and_then(|profiles| profiles.get(name)) else {
+        return Ok(None);
+    };
+    let profile = profile.as_table().ok_or(format!("profile.{} should be a table", name))?;
+    let mut bundle = BuildProfile::default();
+    for (key, value) in profile {
+        let flag = || value.as_bool().ok_or(format!("profile.{}.{} should be true or false", name, key));
+        let path = || value.as_str().map(str::to_string).ok_or(format!("profile.{}.{} should be a path", name, key));
+        match key.as_str() {
+            "cache" => bundle.cache = flag()?,
+            "cache_dir" => bundle.cache_dir = Some(path()?),
+            "self_check" => bundle.self_check = flag()?,
+            "verify" => bundle.verify = flag()?,
+            "require_match" => bundle.require_match = flag()?,
+            "antitamper_config" => bundle.antitamper_config = Some(path()?),
+            _ => return Err(format!("unknown option profile.{}.{}", name, key)),
+        }
+    }
+    Ok(Some(bundle))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_profiles() {
+        let builtin = include_str!("build_profiles.toml");
+        assert_eq!(find(builtin, "dev").unwrap(), Some(BuildProfile{cache: true, ..Default::default()}));
+        assert_eq!(find(builtin, "matching").unwrap(), Some(BuildProfile{self_check: true, require_match: true, ..Default::default()}));
+        assert_eq!(find(builtin, "ci").unwrap(), None);
+    }
+
+    #[test]
+    fn project_profiles() {
+        let config = "[profile.ci]\ncache_dir = \"build/cache\"\nverify = true\nantitamper_config = \"slots.toml\"\n";
+        assert_eq!(find(config, "ci").unwrap(), Some(BuildProfile{cache_dir: Some(String::from("build/cache")), verify: true, antitamper_config: Some(String::from("slots.toml")), ..Default::default()}));
+        assert!(find("[profile.ci]\nverify = \"yes\"\n", "ci").is_err());
+        assert!(find("[profile.ci]\neffort = 9\n", "ci").is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use bk_rom_comp::{message, BkRomError, GameId, GameVersion, Overlay, Profile, Symbol};
+use bk_rom_comp::anti_tamper::CrcSlot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+struct Finding {
+    severity: Severity,
+    file: Option<String>,
+    problem: String,
+    action: String,
+}
+
+//checks the inputs for the usual reasons a build fails without building anything
+//returns false if any errors were found
+pub fn run(elf_path: &str, symbols: Result<Vec<Symbol>, BkRomError>, rom_path: &str, output_path: &str, profile: &Profile, anti_tamper: Result<Vec<CrcSlot>, BkRomError>) -> bool {
+    let game_id = &profile.game_id;
+    let mut findings : Vec<Finding> = Vec::new();
+    let mut add = |severity, file: &str, problem: String, action: &str| {
+        findings.push(Finding{severity, file: Some(file.to_string()).filter(|f| !f.is_empty()), problem, action: action.to_string()});
+    };
+
+    let symbols = match symbols {
+        Ok(symbols) => Some(symbols),
+        Err(e) => {
+            add(Severity::Error, elf_path, format!("could not read elf: {}", e), "check the path and rebuild the elf");
+            None
+        },
+    };
+    //read the rom the way compressing does, any byte order or a split directory
+    let mut rom_map = None;
+    let overlay_offsets = symbols.as_ref().map(|symbols| profile.overlay_names().into_iter().map(|name| Overlay::from_elf_symbols(name, symbols)).collect::<Result<Vec<_>, _>>());
+    let rom = match (std::path::Path::new(rom_path).is_dir(), overlay_offsets) {
+        (true, None) => {
+            add(Severity::Error, rom_path, String::from("a split directory can't be assembled without the elf"), "fix the elf first");
+            None
+        },
+        (true, Some(Err(e))) => {
+            add(Severity::Error, rom_path, format!("a split directory can't be assembled: {}", e), "fix the missing symbols first");
+            None
+        },
+        (_, offsets) => match super::read_uncompressed_rom(rom_path, offsets.and_then(Result::ok).as_deref().unwrap_or_default(), &mut rom_map) {
+            Ok(rom) => Some(rom),
+            Err(e) => {
+                add(Severity::Error, rom_path, format!("could not read uncompressed rom: {}", e), "check the path and rebuild the rom");
+                None
+            },
+        },
+    };
+
+    let anti_tamper = match anti_tamper {
+        Ok(slots) => slots,
+        Err(e) => {
+            add(Severity::Error, elf_path, e.to_string(), "describe where the version keeps its checksums with --antitamper-config");
+            Vec::new()
+        },
+    };
+    if let Some(symbols) = &symbols {
+        let missing = bk_rom_comp::missing_symbols(symbols, profile, &anti_tamper);
+        if !missing.is_empty() {
+            add(Severity::Error, elf_path, format!("{} required symbols missing: {}", missing.len(), missing.join(", ")),
+                "make sure the linker script exports the overlay symbols and the elf is not stripped");
+        }
+    }
+
+    if let Some(rom) = &rom {
+        //country code and revision from the rom header
+        let expected = match game_id {
+            GameId::BanjoKazooie(GameVersion::USA) => (b'E', 0),
+            GameId::BanjoKazooie(GameVersion::PAL) => (b'P', 0),
+            GameId::BanjoKazooie(GameVersion::JP) => (b'J', 0),
+            GameId::BanjoKazooie(GameVersion::USARevA) => (b'E', 1),
+        };
+        if let (Some(country), Some(revision)) = (rom.get(0x3E), rom.get(0x3F)) {
+            if (*country, *revision) != expected {
+                add(Severity::Error, rom_path, format!("rom header (country '{}', revision {}) does not match version {}", *country as char, revision, game_id.version_string()),
+                    "pass the matching -v/--game-version");
+            }
+        }
+        if let Some(symbols) = &symbols {
+            let rom_end = profile.overlays.iter()
+                .filter_map(|name| symbols.iter().find(|s| s.name == format!("{}_ROM_END", name)))
+                .map(|s| s.value as usize)
+                .max()
+                .unwrap_or(0);
+            if rom.len() < rom_end {
+                add(Severity::Error, rom_path, format!("rom is 0x{:X} bytes but the elf places overlays up to 0x{:X}", rom.len(), rom_end),
+                    "the rom and elf come from different builds, rebuild both");
+            }
+        }
+    }
+
+    //the rom is produced from the elf so it should never be older
+    let modified = |path: &str| fs::metadata(path).and_then(|m| m.modified()).ok();
+    if let (Some(elf_time), Some(rom_time)) = (modified(elf_path), modified(rom_path)) {
+        if rom_time < elf_time {
+            add(Severity::Warning, rom_path, String::from("uncompressed rom is older than the elf"), "rebuild the rom so it matches the elf");
+        }
+    }
+
+    if !output_path.is_empty() {
+        let output = std::path::Path::new(output_path);
+        let writable = match fs::metadata(output) {
+            Ok(m) => !m.permissions().readonly(),
+            Err(_) => match output.parent().filter(|p| !p.as_os_str().is_empty()) {
+                Some(parent) => fs::metadata(parent).map(|m| !m.permissions().readonly()).unwrap_or(true),
+                None => true,
+            },
+        };
+        if !writable {
+            add(Severity::Error, output_path, String::from("output path is not writable"), "choose another output path or fix its permissions");
+        }
+    }
+
+    if !matches!(game_id, GameId::BanjoKazooie(GameVersion::USA)) {
+        add(Severity::Warning, "", format!("version {} is less tested than us_v10", game_id.version_string()), "compare the output against a known good rom");
+    }
+
+    //most severe first
+    findings.sort_by_key(|f| f.severity);
+    for f in &findings {
+        let msg = format!("{} ({})", f.problem, f.action);
+        match f.severity {
+            Severity::Error => message::error(f.file.as_deref(), &msg),
+            Severity::Warning => message::warning(f.file.as_deref(), &msg),
+        }
+    }
+    if findings.is_empty() {
+        println!("no problems found");
+    }
+    !findings.iter().any(|f| f.severity == Severity::Error)
+}
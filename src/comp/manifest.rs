@@ -0,0 +1,29 @@
+use sha1::Digest;
+use bk_rom_comp::{rom, BkRomError, GameId};
+use crate::version;
+
+fn crc_json(crc: (u32, u32)) -> String {
+    format!("[{}, {}]", crc.0, crc.1)
+}
+
+//describes every overlay of the compressed rom as json, offsets and sizes are in bytes
+//and the rom hashes are of the big endian rom
+pub fn write(path: &str, rom: &rom::Rom, game_id: &GameId) -> Result<(), BkRomError> {
+    let rom_bytes = rom.to_vec()?;
+    let overlays : Vec<String> = rom.overlay_offsets.iter().zip(&rom.overlay_crcs).zip(rom.rzip_bytes.iter().zip(rom.overlay_rom_offsets())).map(|((overlay, (code_crc, data_crc)), (rzip, offset))| {
+        format!(concat!("    {{\"name\": \"{}\", \"uncompressed_rom_start\": {}, \"uncompressed_rom_end\": {}, \"code_size\": {}, \"data_size\": {},\n",
+            "     \"rom_offset\": {}, \"compressed_size\": {}, \"code_crc\": {}, \"data_crc\": {}}}"),
+            overlay.name, overlay.uncompressed_rom.start, overlay.uncompressed_rom.end, overlay.text.len(), overlay.uncompressed_rom.len() - overlay.text.len(),
+            offset, rzip.len(), crc_json(*code_crc), crc_json(*data_crc))
+    }).collect();
+    let manifest = format!(concat!("{{\n",
+        "  \"tool\": \"{}\",\n",
+        "  \"game\": \"{}\",\n",
+        "  \"version\": \"{}\",\n",
+        "  \"rom\": {{\"size\": {}, \"md5\": \"{:x}\", \"sha1\": \"{:x}\"}},\n",
+        "  \"overlays\": [\n{}\n  ]\n",
+        "}}\n"),
+        version::describe(), game_id.game_string(), game_id.version_string(),
+        rom_bytes.len(), md5::compute(&rom_bytes), sha1::Sha1::digest(&rom_bytes), overlays.join(",\n"));
+    std::fs::write(path, manifest).map_err(BkRomError::file(path))
+}
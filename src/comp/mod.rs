@@ -0,0 +1,947 @@
+use std::borrow::Cow;
+use std::fs::{self};
+use clap::builder::PossibleValuesParser;
+use clap::error::ErrorKind;
+use clap::ValueEnum;
+use bk_rom_comp::anti_tamper::CrcSlot;
+use bk_rom_comp::rom::SymbolFormat;
+use crate::{dat, hook, patch, prompt, version};
+use bk_rom_comp::{anti_tamper, cic, find_elf_symbol, message, profile, rom, store, BkRomError, GameId, GameVersion, Overlay, Profile};
+use bk_rom_comp::{as_big_endian, assemble_split, compress_rom, discover_overlays, map_rom, missing_symbols, override_symbols, read_elf_symbols, read_symbol_addrs, rom_from_big_endian, rom_to_big_endian, verify_round_trip, ByteOrder, Symbol};
+
+#[derive(Debug, Clone)]
+struct Config{
+    out_path: String,
+    out_dir: Option<String>,
+    uncomp_rom_path: String,
+    elf_path: String,
+    symbol_out: bool,
+    symbols_format: SymbolFormat,
+    use_cache: bool,
+    cache_dir: Option<String>,
+    cache_url: Option<String>,
+    self_check: bool,
+    dry_run: bool,
+    watch: bool,
+    diff: bool,
+    crc_report: bool,
+    json: bool,
+    doctor: bool,
+    verify_elf: bool,
+    require_match: bool,
+    verify: bool,
+    check_split: Option<String>,
+    compare: Option<String>,
+    out_format: OutFormat,
+    pad_to: Option<usize>,
+    fill: u8,
+    keep_offsets: bool,
+    header_patches: Vec<(usize, Vec<u8>)>,
+    entrypoint_symbol: Option<String>,
+    incremental: bool,
+    emit_patch: Option<(String, String)>,
+    dat_path: Option<String>,
+    manifest_path: Option<String>,
+    report_path: Option<String>,
+    depfile_path: Option<String>,
+    anti_tamper_config: Option<String>,
+    symbols_file: Option<String>,
+    pre_hooks: Vec<String>,
+    post_hooks: Vec<String>,
+    #[cfg(feature = "upload")]
+    upload: Option<String>,
+    game_def: Option<String>,
+    discover_overlays: bool,
+    extra_overlays: Vec<String>,
+    game_id: GameId, 
+}
+
+mod build_profile;
+mod doctor;
+mod manifest;
+mod report;
+mod split_check;
+mod watch;
+
+fn parse_version(version: &str) -> Option<GameId> {
+    profile::PROFILES.iter().find(|p| p.cli_name == version).map(|p| p.game_id.clone())
+}
+
+//-v/--game-version of the subcommands that build from an elf, us.v10 when not given
+pub(crate) fn game_id(version: Option<&str>) -> GameId {
+    version.and_then(parse_version).unwrap_or(GameId::BanjoKazooie(GameVersion::USA))
+}
+
+//8MB, 16MB, ... or a byte count, none to end the rom after the last overlay
+fn parse_pad_to(size: &str) -> Result<PadTo, String> {
+    if size.eq_ignore_ascii_case("none") {
+        return Ok(PadTo(None));
+    }
+    let bytes = match size.strip_suffix("MB").or(size.strip_suffix("mb")) {
+        Some(mb) => mb.parse::<usize>().ok().map(|mb| mb << 20),
+        None => match size.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16).ok(),
+            None => size.parse().ok(),
+        },
+    };
+    bytes.map(|b| PadTo(Some(b))).ok_or(format!("\"{}\" is not a size, expected e.g. 8MB, 32MB, 0x2000000 or none", size))
+}
+
+#[derive(Clone, Copy, Debug)]
+struct PadTo(Option<usize>);
+
+fn parse_fill(byte: &str) -> Result<u8, String> {
+    match byte.strip_prefix("0x").or(byte.strip_prefix("0X")) {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => byte.parse(),
+    }.map_err(|_| format!("\"{}\" is not a byte, expected e.g. 0x00 or 0xFF", byte))
+}
+
+//bytes for a header field, a newtype so clap takes it as one value
+#[derive(Clone, Debug)]
+struct HeaderBytes(Vec<u8>);
+
+fn parse_title(title: &str) -> Result<HeaderBytes, String> {
+    match title.is_ascii() && title.len() <= 20 {
+        true => Ok(HeaderBytes(format!("{:<20}", title).into_bytes())),
+        false => Err(format!("\"{}\" is not a title, expected up to 20 ascii characters", title)),
+    }
+}
+
+fn parse_game_code(code: &str) -> Result<HeaderBytes, String> {
+    match code.len() == 4 && code.bytes().all(|c| c.is_ascii_alphanumeric()) {
+        true => Ok(HeaderBytes(code.as_bytes().to_vec())),
+        false => Err(format!("\"{}\" is not a game code, expected 4 letters or digits, e.g. NBKE", code)),
+    }
+}
+
+fn parse_region(region: &str) -> Result<HeaderBytes, String> {
+    match region.as_bytes() {
+        [letter] if letter.is_ascii_uppercase() => Ok(HeaderBytes(vec![*letter])),
+        _ => Err(format!("\"{}\" is not a region, expected one uppercase letter, e.g. E, P or J", region)),
+    }
+}
+
+pub(crate) fn version_names() -> PossibleValuesParser {
+    PossibleValuesParser::new(profile::PROFILES.iter().map(|p| p.cli_name.as_str()))
+}
+
+pub const AFTER_HELP : &str = concat!(
+    "missing paths (and the version) are prompted for when run from a terminal\n",
+    "output paths may use {game} and {version} placeholders, e.g. build/{game}.{version}.z64\n",
+    "gzip compressed elf files (.elf.gz) are inflated automatically");
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// elf.elf uncompressed/rom.z64 [compressed/rom_or_symbols.z64], the uncompressed
+    /// rom may also be a directory of loose overlay binaries from decompress --split
+    #[arg(value_name = "PATHS")]
+    paths: Vec<String>,
+
+    /// sets version of banjo used [default: us.v10]
+    #[arg(short = 'v', long = "game-version", value_name = "VERSION_ID", value_parser = version_names())]
+    game_version: Option<String>,
+
+    /// exports compressed rom offset symbols to output file instead of compressing rom
+    #[arg(short, long)]
+    symbols: bool,
+
+    /// format of the offset symbols written by -s and --out-dir, ld (GNU ld
+    /// assignments), json, nm or armips (.definelabel)
+    #[arg(long, value_enum, default_value = "ld")]
+    symbols_format: SymbolsFormat,
+
+    /// checks for common setup problems (missing elf symbols, rom/version mismatch,
+    /// rom older than elf, unwritable output) and prints them most severe first
+    /// without building anything, exits with an error if any are found
+    #[arg(long)]
+    doctor: bool,
+
+    /// only checks that the elf has every symbol compressing needs (overlay bounds,
+    /// anti-tamper slots, crc_ROM_START): lists them per overlay, reports all that
+    /// are missing and any range that ends before it starts, a fast preflight for CI
+    #[arg(long, visible_alias = "check-elf")]
+    verify_elf: bool,
+
+    /// prints a Makefile snippet with rules for the compressed rom, the offset
+    /// symbols and a bk-verify target, meant to be included from the decomp
+    /// Makefile (paths and flags are overridable BK_* variables)
+    #[arg(long)]
+    make_rules: bool,
+
+    /// writes the compressed rom, offset symbols, manifest, build report and a build
+    /// stamp into <DIR> using standard names (e.g. banjo.us_v10.z64,
+    /// banjo.us_v10.rzip_symbols.ld, banjo.us_v10.manifest.json,
+    /// banjo.us_v10.report.json, banjo.us_v10.stamp)
+    #[arg(short, long, value_name = "DIR")]
+    out_dir: Option<String>,
+
+    /// reuses compressed overlays from the shared artifact store
+    /// ($BKROM_CACHE_DIR, $XDG_CACHE_HOME/bkrom or ~/.cache/bkrom)
+    #[arg(short, long)]
+    cache: bool,
+
+    /// like --cache but keeps the store in <DIR>, e.g. a per-project or CI cache,
+    /// entries are only reused by the same tool and rarezip version
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<String>,
+
+    /// like --cache but shares the store over http, entries are fetched with GET and
+    /// uploaded with PUT at <URL>/<kind>/<key>, e.g. http://cache.local:8080/bkrom
+    #[arg(long, value_name = "URL", conflicts_with = "cache_dir")]
+    cache_url: Option<String>,
+
+    /// decompresses every blob right after compressing it and fails if it does not
+    /// match the input
+    #[arg(long)]
+    self_check: bool,
+
+    /// fails unless the compressed rom is byte for byte the original rom of the
+    /// selected version (checked by md5)
+    #[arg(long)]
+    require_match: bool,
+
+    /// decompresses the written rom again and compares it against the uncompressed
+    /// rom, reporting the first offset that differs (apart from the crcs compressing
+    /// fills in)
+    #[arg(long)]
+    verify: bool,
+
+    /// before compressing, checks the uncompressed rom against a split.json written by
+    /// decompress --split (its size and the md5 of every piece) and fails on any piece
+    /// changed since, e.g. to catch edits outside the overlays meant to change
+    #[arg(long, value_name = "SPLIT_JSON")]
+    check_split: Option<String>,
+
+    /// byte order of the compressed rom, z64 (big endian), v64 (byteswapped) or
+    /// n64 (little endian), for flashcart menus and tools that expect the latter
+    #[arg(long, value_enum, default_value = "z64")]
+    out_format: OutFormat,
+
+    /// size the compressed rom is padded to, 8MB, 16MB, 32MB, 64MB (or any size in
+    /// bytes), or none to end it after the last overlay; fails if the rom doesn't fit
+    #[arg(long, value_name = "SIZE", value_parser = parse_pad_to, default_value = "16MB")]
+    pad_to: PadTo,
+
+    /// byte the padding after the overlays is filled with, e.g. 0x00 for
+    /// flashcarts or patch formats that prefer zero fill
+    #[arg(long, value_name = "BYTE", value_parser = parse_fill, default_value = "0xFF")]
+    fill: u8,
+
+    /// places each compressed overlay at its retail rom offset (padding the gaps)
+    /// instead of packing them back to back, for hacks that patch a retail rom in place
+    #[arg(long)]
+    keep_offsets: bool,
+
+    /// renames the rom, up to 20 ascii characters in the header's title field
+    #[arg(long, value_name = "TITLE", value_parser = parse_title)]
+    set_title: Option<HeaderBytes>,
+
+    /// sets the 4 character game code, e.g. NBKE (media, 2 letter id, region)
+    #[arg(long, value_name = "CODE", value_parser = parse_game_code)]
+    set_gamecode: Option<HeaderBytes>,
+
+    /// sets the region letter of the game code, e.g. E (north america), P (europe)
+    /// or J (japan)
+    #[arg(long, value_name = "LETTER", value_parser = parse_region)]
+    set_region: Option<HeaderBytes>,
+
+    /// sets the header entrypoint from an elf symbol (e.g. entrypoint or _start)
+    /// instead of copying it from the uncompressed rom, for hacks that move the
+    /// boot segment, the boot code's offset (6103, 6106) is added
+    #[arg(long, value_name = "SYMBOL")]
+    entrypoint_symbol: Option<String>,
+
+    /// when the output rom already exists, only rewrites the bytes that changed
+    /// (usually a few overlays and the crcs) instead of the whole rom, z64 output only
+    #[arg(long)]
+    incremental: bool,
+
+    /// also writes a patch from <REFERENCE> (e.g. the retail rom) to the compressed
+    /// output, the format is picked from the extension (.bps or .ips)
+    #[arg(long, num_args = 2, value_names = ["REFERENCE", "PATCH"])]
+    emit_patch: Option<Vec<String>>,
+
+    /// compares the compressed rom against a known-good one (e.g. the retail rom)
+    /// and reports which overlays and other regions match, fails on any mismatch
+    #[arg(long, value_name = "REFERENCE_ROM")]
+    compare: Option<String>,
+
+    /// turns on a named bundle of options, more can be given on top; dev (--cache),
+    /// release (--self-check) and matching (--self-check --require-match) are built in,
+    /// a [profile.<NAME>] table in bkrom.toml adds or redefines them
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// reads the --profile definitions from <FILE> instead of ./bkrom.toml
+    #[arg(long, value_name = "FILE", requires = "profile")]
+    profile_config: Option<String>,
+
+    /// compresses and prints the rom layout without writing anything
+    #[arg(short = 'n', long)]
+    dry_run: bool,
+
+    /// keeps running and compresses again whenever the elf, uncompressed rom or a
+    /// config file changes, combine with --post-hook to relaunch an emulator
+    #[arg(long, conflicts_with_all = ["doctor", "verify_elf"])]
+    watch: bool,
+
+    /// dry run that also reports which regions of the existing output rom
+    /// (header crc, overlays, padding, ...) would change
+    #[arg(long)]
+    diff: bool,
+
+    /// prints each overlay's code and data crc pairs (the values the anti-tamper
+    /// slots are filled from) in compressed rom order
+    #[arg(long)]
+    crc_report: bool,
+
+    /// prints the crc report as json
+    #[arg(long, requires = "crc_report")]
+    json: bool,
+
+    /// writes a No-Intro style dat <game> entry (size, crc32, md5, sha1) for the
+    /// compressed rom
+    #[arg(long, value_name = "OUT_DAT")]
+    dat: Option<String>,
+
+    /// writes a json manifest of the compressed rom: each overlay's uncompressed
+    /// range, compressed offset and size, code/data crcs and the rom's hashes
+    #[arg(long, value_name = "OUT_JSON")]
+    manifest: Option<String>,
+
+    /// writes a json build report: each overlay's uncompressed and compressed size
+    /// and the space left in the rom, `bkrom stats` summarizes a directory of them
+    #[arg(long, value_name = "OUT_JSON")]
+    report: Option<String>,
+
+    /// writes a make/ninja depfile listing the elf, uncompressed rom and every
+    /// config file read, so builds know when the rom needs compressing again
+    #[arg(long, value_name = "OUT_D")]
+    depfile: Option<String>,
+
+    /// reads the anti-tamper slots (which symbols receive which overlay's code/data
+    /// crc) from a TOML descriptor instead of the built in table of the version,
+    /// for hacks that moved them or versions without a built in table
+    #[arg(long, value_name = "FILE")]
+    antitamper_config: Option<String>,
+
+    /// reads symbols from a splat style symbol_addrs.txt (name = 0xADDRESS; lines)
+    /// on top of the elf's, replacing the elf's address of any symbol in both
+    #[arg(long, value_name = "FILE")]
+    symbols_file: Option<String>,
+
+    /// reads the version's overlays, rom order, hashes and anti-tamper slots from a
+    /// TOML game definition (same format as the built in src/profile/*.toml)
+    /// instead of the built in ones, e.g. for a hack or a version this build
+    /// doesn't know yet
+    #[arg(long, value_name = "FILE", conflicts_with = "game_version")]
+    game_def: Option<String>,
+
+    /// builds the overlay list from the elf's <name>_ROM_START/_ROM_END/_TEXT_START...
+    /// symbols instead of the game definition, for hacks that add or rename overlays
+    #[arg(long)]
+    discover_overlays: bool,
+
+    /// compresses a hack's own overlay after the stock ones (after emptyLvl),
+    /// may be repeated, the elf needs the overlay's usual <name>_ROM_START... symbols
+    #[arg(long = "extra-overlay", value_name = "NAME")]
+    extra_overlays: Vec<String>,
+
+    /// fails unless this is exactly the given tool version, for pinning the tool
+    /// that produces matching builds
+    #[arg(long, value_name = "VERSION")]
+    require_tool_version: Option<String>,
+
+    /// runs a shell command before the build, may be repeated,
+    /// {elf}, {input} and {output} are replaced with the corresponding paths
+    #[arg(long, value_name = "CMD")]
+    pre_hook: Vec<String>,
+
+    /// runs a shell command after the build, may be repeated,
+    /// e.g. --post-hook "cp {output} /media/sdcard/"
+    #[arg(long, value_name = "CMD")]
+    post_hook: Vec<String>,
+
+    /// boots the finished rom on a flashcart attached over USB (EverDrive, 64drive, ...)
+    /// once every check passed, by running <CMD> with {output} replaced,
+    /// $BKROM_UPLOADER or "UNFLoader -r {output}" when no command is given
+    #[cfg(feature = "upload")]
+    #[arg(long, value_name = "CMD", num_args = 0..=1, default_missing_value = "")]
+    upload: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutFormat {
+    Z64,
+    V64,
+    N64,
+}
+
+impl OutFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutFormat::Z64 => "z64",
+            OutFormat::V64 => "v64",
+            OutFormat::N64 => "n64",
+        }
+    }
+
+    fn byte_order(self) -> ByteOrder {
+        match self {
+            OutFormat::Z64 => ByteOrder::BigEndian,
+            OutFormat::V64 => ByteOrder::ByteSwapped,
+            OutFormat::N64 => ByteOrder::LittleEndian,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SymbolsFormat {
+    Ld,
+    Json,
+    Nm,
+    Armips,
+}
+
+impl SymbolsFormat {
+    fn symbol_format(self) -> SymbolFormat {
+        match self {
+            SymbolsFormat::Ld => SymbolFormat::Ld,
+            SymbolsFormat::Json => SymbolFormat::Json,
+            SymbolsFormat::Nm => SymbolFormat::Nm,
+            SymbolsFormat::Armips => SymbolFormat::Armips,
+        }
+    }
+}
+
+//bkrom compress [-v pal] bk.elf bk.uncompressed.z64 bk.compressed.z64
+//bkrom compress [-v pal] -o build/ bk.elf bk.uncompressed.z64
+impl Config{
+    //elf symbols with --symbols-file applied on top
+    fn symbols(&self) -> Result<Vec<Symbol>, BkRomError> {
+        let mut symbols = read_elf_symbols(&self.elf_path)?;
+        if let Some(path) = &self.symbols_file {
+            override_symbols(&mut symbols, read_symbol_addrs(path)?);
+        }
+        Ok(symbols)
+    }
+
+    fn form_args(args : Args) -> Result<Self, BkRomError>{
+        let usage_error = |msg: &str| -> ! {crate::usage_error("compress", ErrorKind::MissingRequiredArgument, msg)};
+
+        if args.make_rules {
+            print!("{}", include_str!("rules.mk"));
+            std::process::exit(0);
+        }
+        if let Some(v) = &args.require_tool_version {
+            version::require(v)?;
+        }
+
+        let mut config = Config{
+            game_id : GameId::BanjoKazooie(GameVersion::USA),
+            elf_path : String::new(),
+            uncomp_rom_path : String::new(),
+            out_path : String::new(),
+            out_dir : args.out_dir,
+            symbol_out : args.symbols,
+            symbols_format : args.symbols_format.symbol_format(),
+            use_cache : args.cache,
+            cache_dir : args.cache_dir,
+            cache_url : args.cache_url,
+            self_check : args.self_check,
+            dry_run : args.dry_run || args.diff,
+            watch : args.watch,
+            diff : args.diff,
+            crc_report : args.crc_report,
+            json : args.json,
+            doctor : args.doctor,
+            verify_elf : args.verify_elf,
+            require_match : args.require_match,
+            verify : args.verify,
+            check_split : args.check_split,
+            compare : args.compare,
+            out_format : args.out_format,
+            pad_to : args.pad_to.0,
+            fill : args.fill,
+            keep_offsets : args.keep_offsets,
+            //the region letter is the last one of the game code, so it goes second
+            header_patches : [(0x20, args.set_title), (0x3B, args.set_gamecode), (0x3E, args.set_region)].into_iter()
+                .filter_map(|(offset, bytes)| Some((offset, bytes?.0))).collect(),
+            entrypoint_symbol : args.entrypoint_symbol,
+            incremental : args.incremental,
+            emit_patch : args.emit_patch.map(|paths| (paths[0].clone(), paths[1].clone())),
+            dat_path : args.dat,
+            manifest_path : args.manifest,
+            report_path : args.report,
+            depfile_path : args.depfile,
+            anti_tamper_config : args.antitamper_config,
+            symbols_file : args.symbols_file,
+            game_def : args.game_def,
+            discover_overlays : args.discover_overlays,
+            extra_overlays : args.extra_overlays,
+            pre_hooks : args.pre_hook,
+            post_hooks : args.post_hook,
+            #[cfg(feature = "upload")]
+            upload : args.upload,
+        };
+        if let Some(name) = &args.profile {
+            config.apply_build_profile(build_profile::load(name, args.profile_config.as_deref())?);
+        }
+        let version_set = args.game_version.is_some() || config.game_def.is_some();
+        if let Some(v) = &args.game_version {
+            config.game_id = parse_version(v).unwrap();
+        }
+
+        let mut positional = args.paths;
+        let needs_out_path = config.out_dir.is_none() && !config.doctor && !config.verify_elf;
+        let missing = positional.len() < if config.verify_elf {1} else if needs_out_path {3} else {2};
+        if missing && prompt::is_interactive() {
+            //ask for whatever is missing instead of bailing out with the help text
+            if positional.is_empty() {
+                positional.push(prompt::ask("Path to ELF", prompt::existing_file)?);
+            }
+            if positional.len() < 2 && !config.verify_elf {
+                positional.push(prompt::ask("Path to uncompressed ROM", prompt::existing_file)?);
+            }
+            if !version_set {
+                config.game_id = prompt::ask("Version (us.v10 | pal | jp | us.v11) [us.v10]", |v| {
+                    parse_version(if v.is_empty() {"us.v10"} else {v}).ok_or(format!("Unknown version \"{}\"", v))
+                })?;
+            }
+            if needs_out_path && positional.len() < 3 {
+                positional.push(prompt::ask("Path to output ROM", prompt::any_path)?);
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        config.elf_path = positional.next().unwrap_or_else(|| usage_error("No input ELF path specified"));
+        if !config.verify_elf {
+            config.uncomp_rom_path = positional.next().unwrap_or_else(|| usage_error("No input ROM path specified"));
+        }
+        if needs_out_path {
+            config.out_path = positional.next().unwrap_or_else(|| usage_error("No output path specified"));
+        } else if config.doctor && config.out_dir.is_none() {
+            //optional, checked for writability when given
+            config.out_path = positional.next().unwrap_or_default();
+        }
+        if let Some(extra) = positional.next() {
+            crate::usage_error("compress", ErrorKind::TooManyValues, &format!("unexpected argument \"{}\"", extra));
+        }
+        //byteswapped output is converted in memory and rewritten as a whole
+        if config.incremental && config.out_format != OutFormat::Z64 {
+            crate::usage_error("compress", ErrorKind::ArgumentConflict, &format!("--incremental only updates z64 roms, drop it or use --out-format z64 instead of {}", config.out_format.extension()));
+        }
+        //ips offsets stop at 16MiB, a rom padded past that can only get a bps patch
+        if let (Some((_, patch_path)), Some(size)) = (&config.emit_patch, config.pad_to) {
+            if patch::PatchFormat::from_path(patch_path) == patch::PatchFormat::Ips && size > patch::IPS_MAX_SIZE {
+                crate::usage_error("compress", ErrorKind::ArgumentConflict, &format!("an IPS patch can't address a rom padded to 0x{:X} bytes (--pad-to), write \"{}\" as a .bps patch or pad to 16MB", size, patch_path));
+            }
+        }
+        Ok(config)
+    }
+}
+
+impl Config{
+    fn profile(&self) -> Result<Profile, BkRomError> {
+        match &self.game_def {
+            Some(path) => profile::load(path),
+            None => Ok(profile::builtin(&self.game_id).clone()),
+        }
+    }
+
+    //patching the wrong symbols would build a rom that fails its own checksum checks
+    fn anti_tamper_slots(&self, profile: &Profile) -> Result<Vec<CrcSlot>, BkRomError> {
+        match &self.anti_tamper_config {
+            Some(path) => anti_tamper::load(path),
+            None => profile.anti_tamper_slots().map(<[CrcSlot]>::to_vec),
+        }
+    }
+
+    //named option bundles, options given on the command line are added on top
+    fn apply_build_profile(&mut self, profile: build_profile::BuildProfile) {
+        self.use_cache |= profile.cache;
+        self.cache_dir = self.cache_dir.take().or(profile.cache_dir);
+        self.self_check |= profile.self_check;
+        self.verify |= profile.verify;
+        self.require_match |= profile.require_match;
+        self.anti_tamper_config = self.anti_tamper_config.take().or(profile.antitamper_config);
+    }
+}
+
+//lists every required symbol per overlay, then reports all the missing ones and inverted
+//overlay ranges at once, returns false if there were any
+fn verify_elf(elf_path: &str, symbols: Result<Vec<Symbol>, BkRomError>, profile: &Profile, anti_tamper: Result<Vec<CrcSlot>, BkRomError>) -> bool {
+    let symbols = match symbols {
+        Ok(symbols) => symbols,
+        Err(e) => {
+            message::error(Some(elf_path), &e.to_string());
+            return false;
+        },
+    };
+    let (anti_tamper, anti_tamper_ok) = match anti_tamper {
+        Ok(slots) => (slots, true),
+        Err(e) => {
+            message::error(Some(elf_path), &e.to_string());
+            (Vec::new(), false)
+        },
+    };
+
+    let mut groups : Vec<(String, Vec<String>)> = profile.overlay_names().into_iter().chain(["boot_bk_boot"]).map(|name| {
+        let mut names = Overlay::symbol_names(name).to_vec();
+        //core1 uses its data start as text end
+        names.dedup();
+        (name.to_string(), names)
+    }).collect();
+    groups.push((String::from("anti-tamper"), anti_tamper.iter().map(|s| s.symbol.clone()).chain([String::from("crc_ROM_START")]).collect()));
+
+    let width = groups.iter().flat_map(|(_, names)| names.iter().map(String::len)).max().unwrap_or(0);
+    for (group, names) in &groups {
+        println!("{}", group);
+        for name in names {
+            match find_elf_symbol(&symbols, name) {
+                Ok(sym) => println!("  {:<width$} 0x{:08X}", name, sym.value, width = width),
+                Err(_) => println!("  {:<width$} missing", name, width = width),
+            }
+        }
+    }
+
+    let missing = missing_symbols(&symbols, profile, &anti_tamper);
+    missing.iter().for_each(|name| message::error(Some(elf_path), &format!("missing symbol {}", name)));
+
+    //every overlay with a full set of symbols is checked, whatever else is missing
+    let mut inverted = 0;
+    for name in profile.overlay_names().into_iter().chain(["boot_bk_boot"]) {
+        let Ok(info) = Overlay::from_elf_symbols(name, &symbols) else {
+            continue;
+        };
+        for (section, range) in [("text", &info.text), ("data", &info.data), ("bss", &info.bss), ("rom", &info.uncompressed_rom)] {
+            if range.start > range.end {
+                message::error(Some(elf_path), &format!("{} {} ends before it starts (0x{:08X}..0x{:08X})", name, section, range.start, range.end));
+                inverted += 1;
+            }
+        }
+    }
+
+    let required : usize = groups.iter().map(|(_, names)| names.len()).sum();
+    let ok = missing.is_empty() && inverted == 0 && anti_tamper_ok;
+    match ok {
+        true => println!("{}: all {} required symbols present", elf_path, required),
+        false => println!("{}: {} of {} required symbols missing, {} inverted ranges", elf_path, missing.len(), required, inverted),
+    }
+    ok
+}
+
+pub fn run(args : Args) -> Result<(), BkRomError> {
+    //parse command line args
+    let config = Config::form_args(args)?;
+    if config.watch {
+        let inputs : Vec<&str> = [Some(&config.elf_path), Some(&config.uncomp_rom_path), config.game_def.as_ref(), config.anti_tamper_config.as_ref(), config.symbols_file.as_ref()]
+            .into_iter().flatten().map(String::as_str).collect();
+        //a failed build reports its error and waits for the next change
+        watch::run(&inputs, || if let Err(e) = build(config.clone()) {
+            message::error(None, &e.to_string());
+        });
+    }
+    build(config)
+}
+
+fn build(mut config: Config) -> Result<(), BkRomError> {
+    let mut profile = config.profile()?;
+    if config.discover_overlays {
+        profile.set_overlays(discover_overlays(&config.symbols()?)?);
+        log::info!("overlays: {}", profile.overlays.join(" "));
+    }
+    if !config.extra_overlays.is_empty() {
+        let mut overlays = profile.overlays.clone();
+        overlays.extend(config.extra_overlays.iter().filter(|name| !profile.overlays.contains(name)).cloned());
+        profile.set_overlays(overlays);
+    }
+    config.game_id = profile.game_id.clone();
+    config.out_path = config.game_id.expand_template(&config.out_path);
+    config.out_dir = config.out_dir.map(|dir| config.game_id.expand_template(&dir));
+    let output_path = match &config.out_dir {
+        Some(dir) => std::path::Path::new(dir).join(format!("{}.{}", config.game_id.artifact_stem(), config.out_format.extension())).to_string_lossy().into_owned(),
+        None => config.out_path.clone(),
+    };
+    if config.verify_elf {
+        std::process::exit(if verify_elf(&config.elf_path, config.symbols(), &profile, config.anti_tamper_slots(&profile)) {0} else {1});
+    }
+    if config.doctor {
+        let ok = doctor::run(&config.elf_path, config.symbols(), &config.uncomp_rom_path, &output_path, &profile, config.anti_tamper_slots(&profile));
+        std::process::exit(if ok {0} else {1});
+    }
+    let hook_placeholders = [("elf", config.elf_path.as_str()), ("input", config.uncomp_rom_path.as_str()), ("output", output_path.as_str())];
+    config.pre_hooks.iter().try_for_each(|cmd| hook::run(cmd, &hook_placeholders))?;
+
+    let symbols = config.symbols()?;
+
+    //overlays offsets from elf symbols
+    let overlay_names = profile.overlay_names();
+    let overlay_offsets = overlay_names.iter().map(|ovrly_name| {Overlay::from_elf_symbols(ovrly_name, &symbols)}).collect::<Result<Vec<Overlay>, _>>()?;
+
+    let mut uncompressed_map = None;
+    let uncompressed_rom = read_uncompressed_rom(&config.uncomp_rom_path, &overlay_offsets, &mut uncompressed_map)?;
+    if let Some(path) = &config.check_split {
+        split_check::check(path, &uncompressed_rom)?;
+    }
+
+    let store = match (&config.cache_url, &config.cache_dir, config.use_cache) {
+        (Some(url), _, _) => Some(store::Store::open_http(url)?),
+        (None, Some(dir), _) => Some(store::Store::open(dir)),
+        (None, None, true) => Some(store::Store::open_default().ok_or(BkRomError::NoCacheDir)?),
+        (None, None, false) => None,
+    };
+
+    let anti_tamper = config.anti_tamper_slots(&profile)?;
+
+    let mut rom = compress_rom(&symbols, &uncompressed_rom, &profile, &anti_tamper, store.as_ref(), config.self_check)?;
+    rom.pad_to = config.pad_to;
+    rom.fill = config.fill;
+    rom.header_patches = config.header_patches.clone();
+    if let Some(name) = &config.entrypoint_symbol {
+        let cic_type = cic::identify(&uncompressed_rom).ok_or(BkRomError::BadRom(String::from("unknown cic bootcode, can't tell the entrypoint offset")))?;
+        let entrypoint = (find_elf_symbol(&symbols, name)?.value as u32).wrapping_add(cic_type.entrypoint_offset());
+        log::debug!("entrypoint {} => 0x{:08X}", name, entrypoint);
+        rom.header_patches.push((0x08, entrypoint.to_be_bytes().to_vec()));
+    }
+    if config.keep_offsets {
+        rom.slots = Some(profile.overlay_slots().ok_or_else(|| BkRomError::UnsupportedVersion(
+            format!("no retail overlay offsets are known for {} with this overlay list, add overlay_offsets to a --game-def", config.game_id.version_string())))?);
+    }
+
+    if config.crc_report {
+        print_crc_report(&rom, config.json);
+    }
+
+    let compare_error = match &config.compare {
+        Some(reference_path) => {
+            let reference_map = map_rom(reference_path)?;
+            let reference = as_big_endian(&reference_map)?;
+            match compare(&rom, &reference)? {
+                true => None,
+                false => Some(BkRomError::HashMismatch{expected: format!("{:x}", md5::compute(&reference)), actual: format!("{:x}", md5::compute(rom.to_vec()?))}),
+            }
+        },
+        None => None,
+    };
+
+    if config.dry_run {
+        print_layout(&rom);
+        if config.diff {
+            let existing = rom_to_big_endian(fs::read(&output_path).map_err(BkRomError::file(&output_path))?)?;
+            print_diff(&rom, &existing)?;
+        }
+        return compare_error.map_or(Ok(()), Err);
+    }
+
+    match &config.out_dir {
+        Some(dir) => {
+            //write every artifact using standard names
+            fs::create_dir_all(dir)?;
+            let artifact = |ext: &str| std::path::Path::new(dir).join(format!("{}.{}", config.game_id.artifact_stem(), ext));
+            rom.write_symbols(&artifact(&format!("rzip_symbols.{}", config.symbols_format.extension())), config.symbols_format)?;
+            write_rom(&rom, &artifact(config.out_format.extension()), config.out_format, config.incremental)?;
+            if let Some(dat_path) = &config.dat_path {
+                dat::write_entry(dat_path, &artifact(config.out_format.extension()))?;
+            }
+            manifest::write(&artifact("manifest.json").to_string_lossy(), &rom, &config.game_id)?;
+            report::write(&artifact("report.json").to_string_lossy(), &rom, &config.game_id)?;
+            fs::write(artifact("stamp"), format!("{}\n{}\n{}\n", version::describe(), config.elf_path, config.uncomp_rom_path))?;
+        },
+        None if config.symbol_out => {
+            //only generate symbol file
+            rom.write_symbols(std::path::Path::new(&config.out_path), config.symbols_format)?;
+        },
+        None => {
+            write_rom(&rom, std::path::Path::new(&config.out_path), config.out_format, config.incremental)?;
+            if let Some(dat_path) = &config.dat_path {
+                dat::write_entry(dat_path, std::path::Path::new(&config.out_path))?;
+            }
+        },
+    }
+
+    //patch from a reference compressed rom to the output
+    if let (Some((reference_path, patch_path)), false) = (&config.emit_patch, config.symbol_out) {
+        let reference = fs::read(reference_path).map_err(BkRomError::file(reference_path))?;
+        let compressed = fs::read(&output_path).map_err(BkRomError::file(&output_path))?;
+        let patch_bytes = patch::create(patch::PatchFormat::from_path(patch_path), &reference, &compressed)?;
+        fs::write(patch_path, patch_bytes).map_err(BkRomError::file(patch_path))?;
+    }
+
+    if let Some(manifest_path) = &config.manifest_path {
+        manifest::write(manifest_path, &rom, &config.game_id)?;
+    }
+
+    if let Some(report_path) = &config.report_path {
+        report::write(report_path, &rom, &config.game_id)?;
+    }
+
+    if let Some(depfile_path) = &config.depfile_path {
+        write_depfile(depfile_path, &output_path, &config)?;
+    }
+
+    if let Some(e) = compare_error {
+        return Err(e);
+    }
+
+    let output = match config.symbol_out || !(config.verify || config.require_match) {
+        true => None,
+        false => Some(map_rom(&output_path)?),
+    };
+    //checks work on the big endian rom whatever the output format
+    let output = output.as_ref().map(|output| as_big_endian(output)).transpose()?;
+
+    if let (true, Some(output)) = (config.verify, &output) {
+        verify_round_trip(output, &uncompressed_rom, &profile, &symbols, &anti_tamper)?;
+        log::info!("{}: decompresses back to \"{}\"", output_path, config.uncomp_rom_path);
+    }
+
+    if let (true, Some(output)) = (config.require_match, &output) {
+        let expected = profile.compressed_md5.as_deref().ok_or_else(|| BkRomError::UnsupportedVersion(
+            format!("the game definition has no compressed_md5 for {} to match against", config.game_id.version_string())))?;
+        let digest = format!("{:x}", md5::compute(output));
+        if digest != expected {
+            return Err(BkRomError::HashMismatch{expected: expected.to_string(), actual: digest});
+        }
+        log::info!("{}: matches the original {} rom", output_path, config.game_id.version_string());
+    }
+
+    #[cfg(feature = "upload")]
+    if let Some(cmd) = &config.upload {
+        upload(cmd, &output_path)?;
+    }
+
+    config.post_hooks.iter().try_for_each(|cmd| hook::run(cmd, &hook_placeholders))?;
+    Ok(())
+}
+
+//the usb protocols differ per cart, so the transfer is left to a loader that speaks them all
+#[cfg(feature = "upload")]
+fn upload(cmd: &str, output_path: &str) -> Result<(), BkRomError> {
+    let cmd = match cmd {
+        "" => std::env::var("BKROM_UPLOADER").unwrap_or_else(|_| String::from("UNFLoader -r {output}")),
+        cmd => cmd.to_string(),
+    };
+    log::info!("uploading {}", output_path);
+    hook::run(&cmd, &[("output", output_path)])
+}
+
+//the uncompressed rom in any byte order, or a directory holding loose overlay binaries
+//(e.g. from decompress --split), map keeps the mapped file alive
+pub(crate) fn read_uncompressed_rom<'a>(path: &str, overlay_offsets: &[Overlay], map: &'a mut Option<memmap2::Mmap>) -> Result<Cow<'a, [u8]>, BkRomError> {
+    match std::path::Path::new(path).is_dir() {
+        true => Ok(Cow::Owned(assemble_split(path, overlay_offsets)?)),
+        false => as_big_endian(map.insert(map_rom(path)?)),
+    }
+}
+
+//"<output>: <inputs>" in make syntax, which ninja reads too
+fn write_depfile(path: &str, output_path: &str, config: &Config) -> Result<(), BkRomError> {
+    let escape = |p: &str| p.replace(' ', "\\ ").replace('$', "$$");
+    let inputs : Vec<String> = [Some(&config.elf_path), Some(&config.uncomp_rom_path), config.game_def.as_ref(),
+        config.anti_tamper_config.as_ref(), config.symbols_file.as_ref(), config.compare.as_ref(), config.emit_patch.as_ref().map(|(reference, _)| reference)]
+        .into_iter().flatten().map(|p| escape(p)).collect();
+    fs::write(path, format!("{}: {}\n", escape(output_path), inputs.join(" \\\n  "))).map_err(BkRomError::file(path))
+}
+
+fn write_rom(rom: &rom::Rom, path: &std::path::Path, format: OutFormat, incremental: bool) -> Result<(), BkRomError>{
+    if format == OutFormat::Z64 && incremental {
+        let rewritten = rom.update_file(path)?;
+        log::info!("{}: rewrote 0x{:X} of 0x{:X} bytes", path.display(), rewritten, rom.rom_size());
+        return Ok(());
+    }
+    if format == OutFormat::Z64 {
+        return rom.write_file(path);
+    }
+    let mut bytes = rom.to_vec()?;
+    rom_from_big_endian(&mut bytes, format.byte_order());
+    fs::write(path, bytes).map_err(BkRomError::file(path))
+}
+
+fn print_layout(rom: &rom::Rom){
+    println!("{:<10} {:>10} {:>9}", "overlay", "rom offset", "size");
+    for ((name, rzip), offset) in rom.overlay_names.iter().zip(&rom.rzip_bytes).zip(rom.overlay_rom_offsets()){
+        println!("{:<10} {:>#10X} {:>#9X}", name, offset, rzip.len());
+    }
+    println!("padding from {:#X}", rom.overlay_end_offset());
+}
+
+fn print_crc_report(rom: &rom::Rom, json: bool){
+    if json {
+        let overlays : Vec<String> = rom.overlay_names.iter().zip(&rom.overlay_crcs).map(|(name, (code, data))| {
+            format!("    {{\"name\": \"{}\", \"code_crc\": [\"0x{:08X}\", \"0x{:08X}\"], \"data_crc\": [\"0x{:08X}\", \"0x{:08X}\"]}}", name, code.0, code.1, data.0, data.1)
+        }).collect();
+        println!("{{\n  \"overlays\": [\n{}\n  ]\n}}", overlays.join(",\n"));
+        return;
+    }
+    println!("{:<10} {:<17}  {:<17}", "overlay", "code crc", "data crc");
+    for (name, (code, data)) in rom.overlay_names.iter().zip(&rom.overlay_crcs){
+        println!("{:<10} {:08X} {:08X}  {:08X} {:08X}", name, code.0, code.1, data.0, data.1);
+    }
+}
+
+//named regions of the compressed rom, for reporting what changed
+fn rom_regions(rom: &rom::Rom) -> Result<Vec<(String, std::ops::Range<usize>)>, BkRomError>{
+    let crc_rom_start = rom.crc_rom_start()?;
+    let mut regions = vec![
+        (String::from("header crc"), 0x10..0x18),
+        (String::from("header"), 0..0x10),
+        (String::from("bootcode"), 0x40..0x1000),
+        (String::from("bk_boot"), rom.bk_boot_info.uncompressed_rom.clone()),
+        (String::from("crc"), crc_rom_start..crc_rom_start + 0x20),
+        (String::from("pre-overlay data"), crc_rom_start + 0x20..rom.overlay_start_offset),
+    ];
+    for ((name, rzip), offset) in rom.overlay_names.iter().zip(&rom.rzip_bytes).zip(rom.overlay_rom_offsets()){
+        regions.push((name.to_string(), offset..offset + rzip.len()));
+    }
+    regions.push((String::from("padding"), rom.overlay_end_offset()..rom.rom_size()));
+    Ok(regions)
+}
+
+//reports which regions of the existing output would change
+fn print_diff(rom: &rom::Rom, existing: &[u8]) -> Result<(), BkRomError>{
+    let new = rom.to_vec()?;
+    let regions = rom_regions(rom)?;
+
+    if existing.len() != new.len() {
+        println!("size: 0x{:X} => 0x{:X}", existing.len(), new.len());
+    }
+    let mut changed = false;
+    for (name, range) in regions {
+        if existing.get(range.clone()) != new.get(range.clone()) {
+            println!("changed: {} (0x{:X}..0x{:X})", name, range.start, range.end);
+            changed = true;
+        }
+    }
+    if !changed {
+        println!("no changes, rebuild is a no-op");
+    }
+    Ok(())
+}
+
+//per region match report against a known-good compressed rom, returns false on any mismatch
+fn compare(rom: &rom::Rom, reference: &[u8]) -> Result<bool, BkRomError>{
+    let new = rom.to_vec()?;
+    if reference.len() != new.len() {
+        println!("size mismatch: 0x{:X} (reference 0x{:X})", new.len(), reference.len());
+    }
+    let mut matched = reference.len() == new.len();
+    for (name, range) in rom_regions(rom)? {
+        match range.clone().find(|i| new.get(*i) != reference.get(*i)) {
+            Some(offset) => {
+                println!("mismatch: {} (0x{:X}..0x{:X}), first difference at 0x{:X}", name, range.start, range.end, offset);
+                matched = false;
+            },
+            None => println!("match:    {}", name),
+        }
+    }
+    //bytes outside every named region, e.g. the rest of the header
+    if matched {
+        if let Some(offset) = (0..new.len()).find(|i| new[*i] != reference[*i]) {
+            println!("mismatch: first difference at 0x{:X}", offset);
+            matched = false;
+        }
+    }
+    Ok(matched)
+}
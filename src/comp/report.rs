@@ -0,0 +1,26 @@
+use bk_rom_comp::{rom, BkRomError, GameId};
+use crate::version;
+
+//sizes of one build, what `bkrom stats` reads back to track how the overlays grow,
+//sizes are in bytes
+pub fn write(path: &str, rom: &rom::Rom, game_id: &GameId) -> Result<(), BkRomError> {
+    let overlays : Vec<String> = rom.overlay_offsets.iter().zip(&rom.rzip_bytes).map(|(overlay, rzip)| {
+        //sections that grew when compressed and were stored as is
+        let stored : Vec<String> = rom.stored.iter().filter(|(name, _)| *name == overlay.name).map(|(_, section)| format!("\"{}\"", section)).collect();
+        format!("    {{\"name\": \"{}\", \"code_size\": {}, \"data_size\": {}, \"compressed_size\": {}, \"stored\": [{}]}}",
+            overlay.name, overlay.text.len(), overlay.uncompressed_rom.len() - overlay.text.len(), rzip.len(), stored.join(", "))
+    }).collect();
+    let rom_size = rom.rom_size();
+    let report = format!(concat!("{{\n",
+        "  \"tool\": \"{}\",\n",
+        "  \"game\": \"{}\",\n",
+        "  \"version\": \"{}\",\n",
+        "  \"rom_size\": {},\n",
+        "  \"overlays_end\": {},\n",
+        "  \"free\": {},\n",
+        "  \"overlays\": [\n{}\n  ]\n",
+        "}}\n"),
+        version::describe(), game_id.game_string(), game_id.version_string(),
+        rom_size, rom.overlay_end_offset(), rom_size.saturating_sub(rom.overlay_end_offset()), overlays.join(",\n"));
+    std::fs::write(path, report).map_err(BkRomError::file(path))
+}
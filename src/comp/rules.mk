@@ -0,0 +1,30 @@
+# generated by bkrom compress --make-rules, include from the decomp Makefile
+# override any of these before the include
+
+BK_ROM_COMPRESS     ?= bkrom compress
+BK_VERSION          ?= us.v10
+BK_ELF              ?= build/$(BK_VERSION)/banjo.$(BK_VERSION).elf
+BK_UNCOMPRESSED_ROM ?= build/$(BK_VERSION)/banjo.$(BK_VERSION).prelim.z64
+BK_COMPRESSED_ROM   ?= build/$(BK_VERSION)/banjo.$(BK_VERSION).z64
+BK_RZIP_SYMBOLS     ?= build/$(BK_VERSION)/banjo.$(BK_VERSION).rzip_symbols.ld
+BK_COMPRESS_FLAGS   ?= --cache
+
+$(BK_COMPRESSED_ROM): $(BK_ELF) $(BK_UNCOMPRESSED_ROM)
+	$(BK_ROM_COMPRESS) -v $(BK_VERSION) $(BK_COMPRESS_FLAGS) --depfile $@.d $(BK_ELF) $(BK_UNCOMPRESSED_ROM) $@
+
+# picks up config files passed in BK_COMPRESS_FLAGS (--game-def, --antitamper-config, ...)
+-include $(BK_COMPRESSED_ROM).d
+
+$(BK_RZIP_SYMBOLS): $(BK_ELF) $(BK_UNCOMPRESSED_ROM)
+	$(BK_ROM_COMPRESS) -v $(BK_VERSION) $(BK_COMPRESS_FLAGS) -s $(BK_ELF) $(BK_UNCOMPRESSED_ROM) $@
+
+bk-compress: $(BK_COMPRESSED_ROM)
+
+bk-rzip-symbols: $(BK_RZIP_SYMBOLS)
+
+# checks the inputs, then compresses with every blob decompressed and compared again
+bk-verify: $(BK_ELF) $(BK_UNCOMPRESSED_ROM)
+	$(BK_ROM_COMPRESS) -v $(BK_VERSION) --doctor $(BK_ELF) $(BK_UNCOMPRESSED_ROM)
+	$(BK_ROM_COMPRESS) -v $(BK_VERSION) --self-check --dry-run $(BK_ELF) $(BK_UNCOMPRESSED_ROM) $(BK_COMPRESSED_ROM)
+
+.PHONY: bk-compress bk-rzip-symbols bk-verify
@@ -0,0 +1,81 @@
+use bk_rom_comp::BkRomError;
+use crate::stats::field;
+
+//one piece of a split.json, e.g. {"file": "core2.text.bin", ..., "rom_start": 4096, "rom_end": 8192, "md5": "..."}
+struct Piece<'a> {
+    file: &'a str,
+    range: std::ops::Range<usize>,
+    md5: &'a str,
+}
+
+//split.json keeps every piece on its own line, as written by decompress --split
+fn pieces(manifest: &str) -> Result<Vec<Piece<'_>>, String> {
+    manifest.lines().filter(|line| line.contains("\"file\": ")).map(|line| {
+        let number = |key: &str| field(line, key).and_then(|v| v.parse().ok()).ok_or(format!("no {} in \"{}\"", key, line.trim()));
+        Ok(Piece {
+            file: field(line, "file").unwrap_or_default(),
+            range: number("rom_start")? .. number("rom_end")?,
+            md5: field(line, "md5").ok_or("no md5 for each piece, split it again with this version of bkrom")?,
+        })
+    }).collect()
+}
+
+//the pieces of rom that no longer match the manifest, with why
+fn mismatches(manifest: &str, rom: &[u8]) -> Result<Vec<String>, String> {
+    let pieces = pieces(manifest)?;
+    let end = pieces.iter().map(|piece| piece.range.end).max().ok_or("no pieces listed")?;
+    let mut mismatches = Vec::new();
+    if rom.len() != end {
+        mismatches.push(format!("  the rom is 0x{:X} bytes, the pieces end at 0x{:X}", rom.len(), end));
+    }
+    for piece in &pieces {
+        match rom.get(piece.range.clone()) {
+            Some(bytes) if format!("{:x}", md5::compute(bytes)) == piece.md5 => {},
+            Some(_) => mismatches.push(format!("  {:<18} 0x{:08X}..0x{:08X} changed", piece.file, piece.range.start, piece.range.end)),
+            None => mismatches.push(format!("  {:<18} 0x{:08X}..0x{:08X} is past the end of the rom", piece.file, piece.range.start, piece.range.end)),
+        }
+    }
+    Ok(mismatches)
+}
+
+//checks the uncompressed rom against the split.json of an earlier decompress, catching
+//edits to pieces that were meant to be left alone before they get compressed
+pub fn check(path: &str, rom: &[u8]) -> Result<(), BkRomError> {
+    let manifest = std::fs::read_to_string(path).map_err(BkRomError::file(path))?;
+    let mismatches = mismatches(&manifest, rom).map_err(|e| BkRomError::BadRom(format!("\"{}\": {}", path, e)))?;
+    if !mismatches.is_empty() {
+        return Err(BkRomError::BadRom(format!("the uncompressed rom no longer matches \"{}\":\n{}", path, mismatches.join("\n"))));
+    }
+    log::info!("uncompressed rom matches {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(rom: &[u8]) -> String {
+        let piece = |file: &str, start: usize, end: usize| format!("{{\"file\": \"{}\", \"vram\": null, \"rom_start\": {}, \"rom_end\": {}, \"md5\": \"{:x}\"}}",
+            file, start, end, md5::compute(&rom[start..end]));
+        format!("{{\n  \"boot\": {},\n  \"overlays\": [\n    {{\"name\": \"core1\",\n     \"text\": {},\n     \"data\": {}}}\n  ]\n}}\n",
+            piece("boot.bin", 0, 0x10), piece("core1.text.bin", 0x10, 0x30), piece("core1.data.bin", 0x30, 0x40))
+    }
+
+    #[test]
+    fn unchanged_rom() {
+        let rom : Vec<u8> = (0..0x40).collect();
+        assert!(mismatches(&manifest(&rom), &rom).unwrap().is_empty());
+    }
+
+    #[test]
+    fn edited_pieces() {
+        let rom : Vec<u8> = (0..0x40).collect();
+        let mut edited = rom.clone();
+        edited[0x20] = 0xFF;
+        let found = mismatches(&manifest(&rom), &edited).unwrap();
+        assert!(found.len() == 1 && found[0].contains("core1.text.bin"));
+        //a shorter rom is reported both as a size change and a piece it cuts off
+        assert_eq!(mismatches(&manifest(&rom), &rom[..0x38]).unwrap().len(), 2);
+        assert!(mismatches(&manifest(&rom).replace(", \"md5\"", ", \"hash\""), &rom).is_err());
+    }
+}
@@ -0,0 +1,31 @@
+//rebuilds whenever one of the inputs changes, polling their modification times
+//so it works the same on every platform and over network mounts
+
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL : Duration = Duration::from_millis(500);
+
+fn modified(paths: &[&str]) -> Vec<Option<SystemTime>> {
+    paths.iter().map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok()).collect()
+}
+
+pub fn run(paths: &[&str], mut build: impl FnMut()) -> ! {
+    let mut last = modified(paths);
+    loop {
+        build();
+        log::info!("watching {} for changes (ctrl-c to stop)", paths.join(", "));
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+            let now = modified(paths);
+            if now == last {
+                continue;
+            }
+            //wait for the linker to finish writing before rebuilding
+            std::thread::sleep(POLL_INTERVAL);
+            if modified(paths) == now {
+                last = now;
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use std::fs;
+use bk_rom_comp::{cic, rom_from_big_endian, BkRomError, ByteOrder};
+
+pub const AFTER_HELP : &str = concat!(
+    "works on any n64 rom in .z64, .v64 or .n64 byte order, the byte order is kept\n",
+    "e.g. as the last step of a decomp Makefile instead of rn64crc");
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// rom whose header crc is rewritten in place
+    #[arg(value_name = "ROM")]
+    path: String,
+}
+
+pub fn run(args: Args) -> Result<(), BkRomError> {
+    let mut rom = fs::read(&args.path).map_err(BkRomError::file(&args.path))?;
+    let order = ByteOrder::detect(&rom).ok_or(BkRomError::BadRom(String::from("not an n64 rom (unknown byte order signature)")))?;
+    if rom.len() < 0x101000 {
+        return Err(BkRomError::BadRom(format!("\"{}\" is too small to hold the crc area", args.path)));
+    }
+    //swapping twice restores the original order
+    rom_from_big_endian(&mut rom, order);
+    let cic_type = cic::fix_crc(&mut rom).ok_or(BkRomError::BadRom(String::from("unknown cic bootcode, can't calculate the header crc")))?;
+    let crc : Vec<String> = rom[0x10 .. 0x18].chunks_exact(4).map(|w| format!("{:08X}", u32::from_be_bytes(w.try_into().unwrap()))).collect();
+    rom_from_big_endian(&mut rom, order);
+    fs::write(&args.path, &rom).map_err(BkRomError::file(&args.path))?;
+    println!("{}: CIC-{} crc {}", args.path, cic_type.name(), crc.join(" "));
+    Ok(())
+}
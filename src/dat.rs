@@ -0,0 +1,66 @@
+//No-Intro style (logiqx xml) dat entries
+use sha1::Digest;
+use bk_rom_comp::BkRomError;
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+//<game> fragment describing a produced rom file
+pub fn game_entry(path: &std::path::Path, bytes: &[u8]) -> String {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let game_name = path.file_stem().and_then(|n| n.to_str()).unwrap_or_default();
+    format!(concat!(
+        "\t<game name=\"{0}\">\n",
+        "\t\t<description>{0}</description>\n",
+        "\t\t<rom name=\"{1}\" size=\"{2}\" crc=\"{3:08x}\" md5=\"{4:x}\" sha1=\"{5:x}\"/>\n",
+        "\t</game>\n"),
+        escape(game_name), escape(file_name), bytes.len(), crc32(bytes), md5::compute(bytes), sha1::Sha1::digest(bytes))
+}
+
+pub fn write_entry(dat_path: &str, rom_path: &std::path::Path) -> Result<(), BkRomError> {
+    let bytes = std::fs::read(rom_path).map_err(BkRomError::file(rom_path))?;
+    std::fs::write(dat_path, game_entry(rom_path, &bytes)).map_err(BkRomError::file(dat_path))
+}
+
+//attribute value from a single xml tag
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(&tag[start .. start + len])
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+//name of the dat game whose rom hashes match bytes
+pub fn find_game(dat: &str, bytes: &[u8]) -> Option<String> {
+    let size = bytes.len().to_string();
+    let crc = format!("{:08x}", crc32(bytes));
+    let md5 = format!("{:x}", md5::compute(bytes));
+    let sha1 = format!("{:x}", sha1::Sha1::digest(bytes));
+
+    dat.split("<game ").skip(1).find_map(|game| {
+        let game_tag = &game[..game.find('>')?];
+        let matched = game.split("<rom ").skip(1).any(|rom| {
+            let rom_tag = &rom[..rom.find('>').unwrap_or(rom.len())];
+            //everything the dat lists has to agree, and more than just the size has to be listed
+            let checks = [("size", &size), ("crc", &crc), ("md5", &md5), ("sha1", &sha1)];
+            let listed : Vec<bool> = checks.iter()
+                .filter_map(|(attr, value)| attribute(rom_tag, attr).map(|v| v.eq_ignore_ascii_case(value)))
+                .collect();
+            listed.len() > 1 && listed.iter().all(|m| *m)
+        });
+        match matched {
+            true => attribute(game_tag, "name").map(unescape),
+            false => None,
+        }
+    })
+}
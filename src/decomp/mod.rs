@@ -0,0 +1,402 @@
+use std::borrow::Cow;
+use std::fs::{self};
+use clap::error::ErrorKind;
+use crate::{dat, hook, patch, progress, prompt, version};
+use bk_rom_comp::{message, offsets, profile, read_elf_symbols, store, BkRomError, GameId, Overlay, Profile};
+use bk_rom_comp::{decompression_layout, map_rom, rom_to_big_endian, rzip_uncompressed_size, strip_wrapper_header};
+use bk_rom_comp::rom::RomImage;
+
+fn get_hash(rom : &[u8]) -> Result<&'static Profile, md5::Digest> {
+    let digest = md5::compute(rom);
+    let hash = format!("{:x}", digest);
+    match profile::PROFILES.iter().find(|p| p.compressed_md5.as_deref() == Some(hash.as_str())) {
+        Some(p) => Ok(p),
+        None => Err(digest)
+    }
+}
+
+fn json_string_list<'a>(items: impl Iterator<Item = &'a str>) -> String {
+    format!("[{}]", items.map(|s| format!("\"{}\"", s)).collect::<Vec<_>>().join(", "))
+}
+
+//everything this build supports as json, for external tools and test harnesses
+fn dump_profiles() {
+    let profiles : Vec<String> = profile::PROFILES.iter().map(|p| {
+        let overlay_offsets = match &p.overlay_offsets {
+            Some(o) => format!("[{}]", o.iter().map(|offset| offset.to_string()).collect::<Vec<_>>().join(", ")),
+            None => String::from("null"),
+        };
+        let anti_tamper_symbols = match &p.anti_tamper {
+            Some(slots) => json_string_list(slots.iter().map(|s| s.symbol.as_str())),
+            None => String::from("null"),
+        };
+        let compressed_md5 = p.compressed_md5.as_ref().map_or(String::from("null"), |md5| format!("\"{}\"", md5));
+        let rom_order_swaps = format!("[{}]", p.rom_order_swaps.iter().map(|(a, b)| json_string_list([p.overlays[*a].as_str(), p.overlays[*b].as_str()].into_iter())).collect::<Vec<_>>().join(", "));
+        format!(concat!("    {{\"game\": \"{}\", \"version\": \"{}\", \"cli_name\": \"{}\", \"compressed_md5\": {}, \"cic\": \"{}\",\n",
+            "     \"overlays\": {}, \"rom_order_swaps\": {},\n",
+            "     \"overlay_offsets\": {}, \"anti_tamper_symbols\": {}}}"),
+            p.game_id.game_string(), p.game_id.version_string(), p.cli_name, compressed_md5, p.cic,
+            json_string_list(p.overlays.iter().map(String::as_str)), rom_order_swaps, overlay_offsets, anti_tamper_symbols)
+    }).collect();
+    println!("{{");
+    println!("  \"tool\": \"{}\",", version::describe());
+    println!("  \"profiles\": [\n{}\n  ]", profiles.join(",\n"));
+    println!("}}");
+}
+
+pub const AFTER_HELP : &str = concat!(
+    "output paths may use {game} and {version} placeholders, e.g. build/{game}.{version}.z64\n",
+    "missing paths are prompted for when run from a terminal");
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// compressed/rom.z64 uncompressed/rom.z64
+    #[arg(value_name = "PATHS", num_args = 0..=2)]
+    paths: Vec<String>,
+
+    /// reuses decompressed overlays from the shared artifact store
+    /// ($BKROM_CACHE_DIR, $XDG_CACHE_HOME/bkrom or ~/.cache/bkrom)
+    #[arg(short, long)]
+    cache: bool,
+
+    /// like --cache but keeps the store in <DIR>, e.g. a per-project or CI cache,
+    /// entries are only reused by the same tool and rarezip version
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<String>,
+
+    /// like --cache but shares the store over http, entries are fetched with GET and
+    /// uploaded with PUT at <URL>/<kind>/<key>, e.g. http://cache.local:8080/bkrom
+    #[arg(long, value_name = "URL", conflicts_with = "cache_dir")]
+    cache_url: Option<String>,
+
+    /// also writes a patch from <REFERENCE> (an uncompressed rom) to the
+    /// decompressed output, the format is picked from the extension (.bps or .ips)
+    #[arg(short, long, num_args = 2, value_names = ["REFERENCE", "PATCH"])]
+    patch: Option<Vec<String>>,
+
+    /// applies a BPS or IPS patch to the compressed rom before decompressing it,
+    /// e.g. to go straight from a retail rom and a hack's patch to a working rom
+    #[arg(long, value_name = "PATCH")]
+    apply_patch: Option<String>,
+
+    /// also writes every decompressed overlay to <DIR> as <overlay>.text.bin and
+    /// <overlay>.data.bin, plus an <overlay>.ld fragment with their offsets in the
+    /// uncompressed rom, for linking overlays that are not decompiled yet as blobs,
+    /// the rom before the overlays as boot.bin and a split.json listing every piece
+    #[arg(long, visible_alias = "split", value_name = "DIR")]
+    export_overlays: Option<String>,
+
+    /// elf of the decomp, fills in each piece's vram address in split.json
+    /// (null without it, a compressed rom doesn't record them)
+    #[arg(long, value_name = "ELF", requires = "export_overlays")]
+    elf: Option<String>,
+
+    /// writes a No-Intro style dat <game> entry (size, crc32, md5, sha1) for the
+    /// decompressed rom
+    #[arg(long, value_name = "OUT_DAT")]
+    dat: Option<String>,
+
+    /// checks the compressed rom's hashes against a No-Intro dat file and prints
+    /// the name of the matching dump, fails if nothing matches
+    #[arg(long, value_name = "IN_DAT")]
+    verify_dat: Option<String>,
+
+    /// prints every supported version (hashes, cic, overlay names and offsets,
+    /// anti-tamper slots) as json, then exits
+    #[arg(long)]
+    dump_profiles: bool,
+
+    /// fails unless this is exactly the given tool version, for pinning the tool
+    /// that produces matching builds
+    #[arg(long, value_name = "VERSION")]
+    require_tool_version: Option<String>,
+
+    /// reads the version's hashes, overlays, offsets and anti-tamper slots from a
+    /// TOML game definition (same format as the built in src/profile/*.toml)
+    /// instead of the built in ones, e.g. for a hack with relocated overlays
+    #[arg(long, value_name = "FILE")]
+    game_def: Option<String>,
+
+    /// reads the compressed overlay offsets from <FILE> instead of the built in
+    /// tables or a header scan, one "<name> <start> <end>" line (hex) per overlay
+    /// in compressed rom order, for hacks with relocated overlays
+    #[arg(long, value_name = "FILE")]
+    offsets: Option<String>,
+
+    /// runs a shell command once the version is known and before the output is
+    /// written, may be repeated, {input} and {output} (with {game} and {version}
+    /// filled in) are replaced with the corresponding paths
+    #[arg(long, value_name = "CMD")]
+    pre_hook: Vec<String>,
+
+    /// runs a shell command after the build, may be repeated,
+    /// e.g. --post-hook "cp {output} /media/sdcard/"
+    #[arg(long, value_name = "CMD")]
+    post_hook: Vec<String>,
+}
+
+#[derive(Debug)]
+struct Config{
+    source_path: String,
+    target_path: String,
+    use_cache: bool,
+    cache_dir: Option<String>,
+    cache_url: Option<String>,
+    patch: Option<(String, String)>,
+    apply_patch: Option<String>,
+    dat_path: Option<String>,
+    verify_dat: Option<String>,
+    export_dir: Option<String>,
+    elf_path: Option<String>,
+    game_def: Option<String>,
+    offsets_path: Option<String>,
+    pre_hooks: Vec<String>,
+    post_hooks: Vec<String>,
+}
+
+//bkrom decompress [-c] bk.compressed.z64 bk.uncompressed.z64
+impl Config{
+    fn form_args(args : Args) -> Result<Self, BkRomError>{
+        let usage_error = |msg: &str| -> ! {crate::usage_error("decompress", ErrorKind::MissingRequiredArgument, msg)};
+
+        if args.dump_profiles {
+            dump_profiles();
+            std::process::exit(0);
+        }
+        if let Some(v) = &args.require_tool_version {
+            version::require(v)?;
+        }
+
+        let mut positional = args.paths;
+        if positional.len() < 2 && prompt::is_interactive() {
+            //ask for whatever is missing instead of bailing out with the help text
+            if positional.is_empty() {
+                positional.push(prompt::ask("Path to compressed ROM", prompt::existing_file)?);
+            }
+            positional.push(prompt::ask("Path to output ROM", prompt::any_path)?);
+        }
+
+        let mut positional = positional.into_iter();
+        Ok(Config{
+            source_path : positional.next().unwrap_or_else(|| usage_error("No compressed ROM path specified")),
+            target_path : positional.next().unwrap_or_else(|| usage_error("No destination ROM path specified")),
+            use_cache : args.cache,
+            cache_dir : args.cache_dir,
+            cache_url : args.cache_url,
+            patch : args.patch.map(|paths| (paths[0].clone(), paths[1].clone())),
+            apply_patch : args.apply_patch,
+            dat_path : args.dat,
+            verify_dat : args.verify_dat,
+            export_dir : args.export_overlays,
+            elf_path : args.elf,
+            game_def : args.game_def,
+            offsets_path : args.offsets,
+            pre_hooks : args.pre_hook,
+            post_hooks : args.post_hook,
+        })
+    }
+}
+
+pub fn run(args : Args) -> Result<(), BkRomError> {
+    //get rom in_path
+    let config = Config::form_args(args)?;
+    let source_path = &config.source_path;
+    let target_path = &config.target_path;
+    let store = match (&config.cache_url, &config.cache_dir, config.use_cache) {
+        (Some(url), _, _) => Some(store::Store::open_http(url)?),
+        (None, Some(dir), _) => Some(store::Store::open(dir)),
+        (None, None, true) => Some(store::Store::open_default().ok_or(BkRomError::NoCacheDir)?),
+        (None, None, false) => None,
+    };
+
+    //create output
+    log::debug!("Decompressing ROM {} => {}", source_path, target_path);
+    
+    //read in binary and convert to big endian
+    let source = map_rom(source_path)?;
+    let source : Cow<[u8]> = match &config.apply_patch {
+        Some(patch_path) => {
+            let patch_bytes = fs::read(patch_path).map_err(BkRomError::file(patch_path))?;
+            Cow::Owned(patch::apply(&source, &patch_bytes).map_err(|e| BkRomError::BadRom(format!("\"{}\": {}", patch_path, e)))?)
+        },
+        None => Cow::Borrowed(&source),
+    };
+    let compressed_rom : Cow<[u8]> = match source.get(0..4) {
+        //already big endian, read straight from the mapping
+        Some([0x80, 0x37, 0x12, 0x40]) => Cow::Borrowed(&source),
+        _ => {
+            let (compressed_rom, header_len) = strip_wrapper_header(source.to_vec());
+            if header_len != 0 {
+                log::info!("notice: stripped 0x{:X} byte wrapper header from \"{}\"", header_len, source_path);
+            }
+            Cow::Owned(rom_to_big_endian(compressed_rom)?)
+        },
+    };
+
+    if let Some(dat_path) = &config.verify_dat {
+        let dat = fs::read_to_string(dat_path).map_err(BkRomError::file(dat_path))?;
+        match dat::find_game(&dat, &compressed_rom) {
+            Some(name) => log::info!("dat match: {}", name),
+            None => return Err(BkRomError::BadRom(format!("\"{}\" does not match any rom in \"{}\"", source_path, dat_path))),
+        }
+    }
+
+    //check game version ?
+    let (profile, known_layout) : (Cow<Profile>, bool) = match (&config.game_def, get_hash(&compressed_rom)) {
+        //trust the given definition's offset table before falling back to a scan
+        (Some(path), _) => (Cow::Owned(profile::load(path)?), true),
+        (None, Ok(profile)) => (Cow::Borrowed(profile), true),
+        (None, Err(digest)) => {
+            //modified rom, the header still says which version it was built from
+            let game_id = GameId::from_header(&compressed_rom).ok_or_else(|| BkRomError::UnsupportedVersion(format!("unknown md5 {:x}", digest)))?;
+            message::warning(Some(source_path), &format!("unknown md5 {:x}, treating it as a modified {} rom", digest, game_id.version_string()));
+            (Cow::Borrowed(profile::builtin(&game_id)), false)
+        },
+    };
+    let game_id = &profile.game_id;
+    log::debug!("Game Identified as {:?}", game_id);
+    let target_path = &game_id.expand_template(target_path);
+    //after the expansion so hooks see the real output path
+    config.pre_hooks.iter().try_for_each(|cmd| hook::run(cmd, &[("input", source_path), ("output", target_path)]))?;
+    if let Some(parent) = std::path::Path::new(target_path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    //get all file offsets
+    let overlay_offsets = match (&config.offsets_path, known_layout) {
+        (Some(path), _) => {
+            let list = fs::read_to_string(path).map_err(BkRomError::file(path))?;
+            Some(offsets::from_list(&compressed_rom, &profile, &list).map_err(|e| BkRomError::BadRom(format!("\"{}\": {}", path, e)))?)
+        },
+        (None, true) => offsets::discover(&compressed_rom, &profile),
+        (None, false) => offsets::scan_offsets(&compressed_rom, &profile),
+    };
+    let overlay_offsets = overlay_offsets.ok_or_else(|| BkRomError::BadRom(format!("file offsets not found for {:?}", game_id)))?;
+    log::info!("overlay offsets from {:?} (confidence: {:?})", overlay_offsets.source, overlay_offsets.confidence);
+    let file_offsets = overlay_offsets.file_offsets;
+
+    let (compressed_overlays, overlay_offsets, rom_len) = decompression_layout(&compressed_rom, &file_offsets, &profile)?;
+
+    //preallocate output and write each overlay at its final offset as it is decompressed
+    let out_file = std::fs::File::create(target_path).map_err(BkRomError::file(target_path))?;
+    out_file.set_len(rom_len as u64)?;
+    out_file.write_at(&compressed_rom[..file_offsets[0]], 0)?;
+
+    let export_dir = config.export_dir.as_ref().map(|dir| game_id.expand_template(dir));
+    let vram = if let Some(dir) = &export_dir {
+        fs::create_dir_all(dir)?;
+        export_linker_fragments(dir, &profile.overlays, &overlay_offsets, rom_len)?;
+        let vram = match &config.elf_path {
+            Some(elf_path) => {
+                let symbols = read_elf_symbols(elf_path)?;
+                Some(profile.overlays.iter().map(|name| Overlay::from_elf_symbols(name, &symbols)).collect::<Result<Vec<Overlay>, _>>()?)
+            },
+            None => None,
+        };
+        fs::write(std::path::Path::new(dir).join("boot.bin"), &compressed_rom[..file_offsets[0]])?;
+        vram
+    } else {
+        None
+    };
+
+    log::debug!("Decompressing overlays...");
+    let bar = progress::bar("decompressing", compressed_overlays.len());
+    std::thread::scope(|scope| {
+        let blocks : Vec<_> = compressed_overlays.iter().zip(overlay_offsets.iter().copied()).enumerate().map(|(i, (ovrly, offset))| {
+            let out_file = &out_file;
+            let store = store.as_ref();
+            let export_dir = export_dir.as_ref();
+            let overlay_names = &profile.overlays;
+            let bar = &bar;
+            scope.spawn(move || -> Result<(), BkRomError> {
+                let bytes = match store {
+                    Some(store) => store.get_or_insert_with("bk_unzip", ovrly, |bytes| rzip_uncompressed_size(ovrly).is_ok_and(|len| bytes.len() == len), || rarezip::bk::unzip(ovrly)),
+                    None => rarezip::bk::unzip(ovrly),
+                };
+                if bytes.len() != rzip_uncompressed_size(ovrly)? {
+                    return Err(BkRomError::BadRom(String::from("Decompressed overlay size does not match rarezip header")));
+                }
+                log::debug!("placing {:8X} bytes at 0x{:08X?}", bytes.len(), offset);
+                out_file.write_at(&bytes, offset)?;
+                if let Some(dir) = export_dir {
+                    fs::write(std::path::Path::new(dir).join(export_name(overlay_names, i, "bin")), &bytes)?;
+                }
+                bar.inc(1);
+                Ok(())
+            })
+        }).collect();
+        blocks.into_iter().try_for_each(|block| block.join().unwrap())
+    })?;
+    bar.finish_and_clear();
+
+    //the manifest hashes every piece, so it is written once they are all in place
+    if let Some(dir) = &export_dir {
+        let decompressed = fs::read(target_path)?;
+        export_split_manifest(dir, game_id, &profile.overlays, vram.as_deref(), &overlay_offsets, &decompressed)?;
+    }
+
+    //patch from a reference uncompressed rom to the decompressed output
+    if let Some((reference_path, patch_path)) = &config.patch {
+        let reference = fs::read(reference_path).map_err(BkRomError::file(reference_path))?;
+        let decompressed = fs::read(target_path)?;
+        let patch_bytes = patch::create(patch::PatchFormat::from_path(patch_path), &reference, &decompressed)?;
+        fs::write(patch_path, patch_bytes)?;
+    }
+
+    if let Some(dat_path) = &config.dat_path {
+        dat::write_entry(dat_path, std::path::Path::new(target_path))?;
+    }
+
+    config.post_hooks.iter().try_for_each(|cmd| hook::run(cmd, &[("input", source_path), ("output", target_path)]))?;
+    Ok(())
+}
+
+//blocks alternate code and data for each overlay in uncompressed rom order
+fn export_name(overlay_names : &[String], block : usize, ext : &str) -> String {
+    format!("{}.{}.{}", overlay_names[block / 2], if block.is_multiple_of(2) {"text"} else {"data"}, ext)
+}
+
+//one ld fragment per overlay declaring where its exported blobs live in the uncompressed rom
+fn export_linker_fragments(dir : &str, overlay_names : &[String], block_offsets : &[usize], rom_len : usize) -> std::io::Result<()> {
+    let block_end = |block : usize| block_offsets.get(block + 1).copied().unwrap_or(rom_len);
+    for (i, name) in overlay_names.iter().enumerate() {
+        let (text, data) = (2*i, 2*i + 1);
+        let fragment = format!(concat!(
+                "/* {name} exported by bkrom decompress, link {text_bin} and {data_bin} as binary blobs */\n",
+                "{name}_TEXT_BIN_ROM_START = 0x{:X};\n",
+                "{name}_TEXT_BIN_ROM_END = 0x{:X};\n",
+                "{name}_DATA_BIN_ROM_START = 0x{:X};\n",
+                "{name}_DATA_BIN_ROM_END = 0x{:X};\n"),
+            block_offsets[text], block_end(text), block_offsets[data], block_end(data),
+            name = name, text_bin = export_name(overlay_names, text, "bin"), data_bin = export_name(overlay_names, data, "bin"));
+        fs::write(std::path::Path::new(dir).join(format!("{}.ld", name)), fragment)?;
+    }
+    Ok(())
+}
+
+//every exported piece with its range and md5 in the uncompressed rom, enough to put the rom
+//back together (compress takes the directory in place of the uncompressed rom) and to check a
+//later build against it (compress --check-split), vram addresses are only in the elf so they
+//are null unless its overlays are given
+fn export_split_manifest(dir : &str, game_id : &GameId, overlay_names : &[String], vram : Option<&[Overlay]>, block_offsets : &[usize], rom : &[u8]) -> std::io::Result<()> {
+    let block_end = |block : usize| block_offsets.get(block + 1).copied().unwrap_or(rom.len());
+    let piece = |file : &str, vram : Option<usize>, start : usize, end : usize| format!("{{\"file\": \"{}\", \"vram\": {}, \"rom_start\": {}, \"rom_end\": {}, \"md5\": \"{:x}\"}}",
+        file, vram.map_or(String::from("null"), |v| v.to_string()), start, end, md5::compute(&rom[start..end]));
+    let overlays : Vec<String> = overlay_names.iter().enumerate().map(|(i, name)| {
+        let (text, data) = (2*i, 2*i + 1);
+        let overlay = vram.map(|overlays| &overlays[i]);
+        format!("    {{\"name\": \"{}\",\n     \"text\": {},\n     \"data\": {}}}", name,
+            piece(&export_name(overlay_names, text, "bin"), overlay.map(|o| o.text.start), block_offsets[text], block_end(text)),
+            piece(&export_name(overlay_names, data, "bin"), overlay.map(|o| o.data.start), block_offsets[data], block_end(data)))
+    }).collect();
+    let manifest = format!(concat!("{{\n",
+        "  \"tool\": \"{}\",\n",
+        "  \"game\": \"{}\",\n",
+        "  \"version\": \"{}\",\n",
+        "  \"boot\": {},\n",
+        "  \"overlays\": [\n{}\n  ]\n",
+        "}}\n"),
+        version::describe(), game_id.game_string(), game_id.version_string(),
+        piece("boot.bin", None, 0, block_offsets[0]), overlays.join(",\n"));
+    fs::write(std::path::Path::new(dir).join("split.json"), manifest)
+}
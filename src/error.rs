@@ -0,0 +1,81 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum BkRomError {
+    Io(std::io::Error),
+    //io error on a file the user named, so the message can say which one
+    File{path: String, error: std::io::Error},
+    //the elf could not be parsed
+    BadElf(String),
+    MissingSymbol(String),
+    //a symbol exists but does not point where it has to, e.g. an anti-tamper slot outside its overlay
+    BadSymbol(String),
+    //a rom or version this tool doesn't know how to handle
+    UnsupportedVersion(String),
+    HashMismatch{expected: String, actual: String},
+    //the rom itself is malformed (not an n64 rom, broken rarezip blocks, ...)
+    BadRom(String),
+    SelfCheckFailed{overlay: String, section: String},
+    //a --pre-hook/--post-hook command failed or could not be started
+    HookFailed{cmd: String, reason: String},
+    //--require-tool-version doesn't match this build
+    ToolVersion{required: String, actual: String},
+    NoCacheDir,
+    //--cache-url isn't an http://host[:port][/path] url
+    BadCacheUrl(String),
+    //stdin closed while prompting for a missing argument
+    NoInput(String),
+    //the overlay list lacks an overlay compressing needs, e.g. core1
+    MissingOverlay(String),
+    //a patch can't be made for this rom, e.g. an IPS patch past 16MiB
+    BadPatch(String),
+    //first offset where the decompressed output differs from the uncompressed input
+    RoundTripMismatch(usize),
+}
+
+impl fmt::Display for BkRomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BkRomError::Io(e) => write!(f, "{}", e),
+            BkRomError::File{path, error} => write!(f, "\"{}\": {}", path, error),
+            BkRomError::BadElf(msg) => write!(f, "could not read elf: {}", msg),
+            BkRomError::MissingSymbol(name) => write!(f, "could not find symbol {} in elf symbols", name),
+            BkRomError::BadSymbol(msg) => write!(f, "{}", msg),
+            BkRomError::UnsupportedVersion(msg) => write!(f, "unsupported version: {}", msg),
+            BkRomError::HashMismatch{expected, actual} => write!(f, "md5 {} does not match the expected {}", actual, expected),
+            BkRomError::BadRom(msg) => write!(f, "{}", msg),
+            BkRomError::SelfCheckFailed{overlay, section} => write!(f, "self-check failed: {} {} does not decompress to its input", overlay, section),
+            BkRomError::HookFailed{cmd, reason} => write!(f, "hook \"{}\" failed ({})", cmd, reason),
+            BkRomError::ToolVersion{required, actual} => write!(f, "tool version {} required but this is {}", required, actual),
+            BkRomError::NoCacheDir => write!(f, "could not locate a cache directory, pass one with --cache-dir"),
+            BkRomError::BadCacheUrl(url) => write!(f, "\"{}\" is not a cache url, expected http://host[:port][/path]", url),
+            BkRomError::NoInput(question) => write!(f, "no input for \"{}\"", question),
+            BkRomError::MissingOverlay(name) => write!(f, "the overlay list has no {} overlay", name),
+            BkRomError::BadPatch(msg) => write!(f, "{}", msg),
+            BkRomError::RoundTripMismatch(offset) => write!(f, "round trip failed: decompressed rom differs from the input at 0x{:X}", offset),
+        }
+    }
+}
+
+impl std::error::Error for BkRomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BkRomError::Io(e) | BkRomError::File{error: e, ..} => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl BkRomError {
+    //for map_err, e.g. fs::read(path).map_err(BkRomError::file(path))
+    pub fn file<P: AsRef<std::path::Path>>(path: P) -> impl FnOnce(std::io::Error) -> BkRomError {
+        let path = path.as_ref().display().to_string();
+        move |error| BkRomError::File{path, error}
+    }
+}
+
+impl From<std::io::Error> for BkRomError {
+    fn from(e: std::io::Error) -> Self {
+        BkRomError::Io(e)
+    }
+}
@@ -0,0 +1,19 @@
+//user supplied pre/post build commands
+use bk_rom_comp::BkRomError;
+
+//runs cmd through the shell after substituting {name} placeholders
+pub fn run(cmd: &str, placeholders: &[(&str, &str)]) -> Result<(), BkRomError> {
+    let cmd = placeholders.iter().fold(cmd.to_string(), |cmd, (name, value)| {
+        cmd.replace(&format!("{{{}}}", name), value)
+    });
+    let status = if cfg!(windows) {
+        std::process::Command::new("cmd").args(["/C", &cmd]).status()
+    } else {
+        std::process::Command::new("sh").args(["-c", &cmd]).status()
+    };
+    match status {
+        Ok(s) if s.success() => Ok(()),
+        Ok(s) => Err(BkRomError::HookFailed{cmd, reason: s.to_string()}),
+        Err(e) => Err(BkRomError::HookFailed{cmd, reason: format!("could not run it: {}", e)}),
+    }
+}
@@ -0,0 +1,71 @@
+use sha1::Digest;
+use bk_rom_comp::{cic, map_rom, as_big_endian, offsets, profile, BkRomError, ByteOrder, GameId};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// rom to identify, in any byte order
+    #[arg(value_name = "ROM")]
+    path: String,
+
+    /// prints the result as json
+    #[arg(long)]
+    json: bool,
+}
+
+fn byte_order_string(order: ByteOrder) -> &'static str {
+    match order {
+        ByteOrder::BigEndian => "z64",
+        ByteOrder::ByteSwapped => "v64",
+        ByteOrder::LittleEndian => "n64",
+    }
+}
+
+fn json_or_null(value: Option<&str>) -> String {
+    value.map_or(String::from("null"), |v| format!("\"{}\"", v))
+}
+
+pub fn run(args: Args) -> Result<(), BkRomError> {
+    let source = map_rom(&args.path)?;
+    let order = ByteOrder::detect(&source).ok_or(BkRomError::BadRom(format!("\"{}\" is not an n64 rom (unknown byte order signature)", args.path)))?;
+    let rom = as_big_endian(&source)?;
+
+    let md5 = format!("{:x}", md5::compute(&rom[..]));
+    let sha1 = format!("{:x}", sha1::Sha1::digest(&rom[..]));
+    //an exact hash match is an original dump, otherwise fall back to the product code
+    let known = profile::PROFILES.iter().find(|p| p.compressed_md5.as_deref() == Some(md5.as_str()));
+    let game_id : Option<GameId> = known.map(|p| p.game_id.clone()).or_else(|| GameId::from_header(&rom));
+    let cic_type = if rom.len() >= 0x101000 { cic::identify(&rom) } else { None };
+    let layout = match (known, &game_id) {
+        (Some(_), _) => "compressed",
+        (None, Some(id)) if offsets::discover(&rom, profile::builtin(id)).is_some() => "compressed",
+        (None, Some(_)) => "decompressed",
+        (None, None) => "unknown",
+    };
+
+    if args.json {
+        println!("{{");
+        println!("  \"path\": \"{}\",", args.path.replace('\\', "\\\\").replace('"', "\\\""));
+        println!("  \"byte_order\": \"{}\",", byte_order_string(order));
+        println!("  \"md5\": \"{}\",", md5);
+        println!("  \"sha1\": \"{}\",", sha1);
+        println!("  \"game\": {},", json_or_null(game_id.as_ref().map(GameId::game_string)));
+        println!("  \"version\": {},", json_or_null(game_id.as_ref().map(GameId::version_string)));
+        println!("  \"exact_match\": {},", known.is_some());
+        println!("  \"cic\": {},", json_or_null(cic_type.as_ref().map(cic::N64CicType::name)));
+        println!("  \"layout\": \"{}\"", layout);
+        println!("}}");
+    } else {
+        println!("{}", args.path);
+        println!("  byte order: {}", byte_order_string(order));
+        println!("  md5:        {}", md5);
+        println!("  sha1:       {}", sha1);
+        match (&game_id, known) {
+            (Some(id), Some(_)) => println!("  game:       {} {}", id.game_string(), id.version_string()),
+            (Some(id), None) => println!("  game:       {} {} (from header, not an original dump)", id.game_string(), id.version_string()),
+            (None, _) => println!("  game:       unknown"),
+        }
+        println!("  cic:        {}", cic_type.as_ref().map_or("unknown", cic::N64CicType::name));
+        println!("  layout:     {}", layout);
+    }
+    Ok(())
+}
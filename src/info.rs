@@ -0,0 +1,75 @@
+use bk_rom_comp::{cic, map_rom, as_big_endian, BkRomError};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// rom whose header is printed, in any byte order
+    #[arg(value_name = "ROM")]
+    path: String,
+
+    /// prints the result as json
+    #[arg(long)]
+    json: bool,
+}
+
+fn region(code: u8) -> &'static str {
+    match code {
+        b'A' => "all",
+        b'D' => "germany",
+        b'E' => "north america",
+        b'F' => "france",
+        b'I' => "italy",
+        b'J' => "japan",
+        b'P' | b'X' | b'Y' => "europe",
+        b'S' => "spain",
+        b'U' => "australia",
+        _ => "unknown",
+    }
+}
+
+//the sdk release the rom was built with, e.g. 0x144B => 2.0K
+fn libultra_version(header: &[u8]) -> Option<String> {
+    match (header[0x0E], header[0x0F]) {
+        (version @ 10..=99, revision @ b'A'..=b'Z') => Some(format!("{}.{}{}", version/10, version%10, revision as char)),
+        _ => None,
+    }
+}
+
+fn json_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+pub fn run(args: Args) -> Result<(), BkRomError> {
+    let source = map_rom(&args.path)?;
+    let rom = as_big_endian(&source)?;
+    let header = rom.get(..0x40).ok_or(BkRomError::BadRom(format!("\"{}\" is too small to hold an n64 header", args.path)))?;
+
+    let word = |offset: usize| u32::from_be_bytes(header[offset .. offset + 4].try_into().unwrap());
+    let printable = |bytes: &[u8]| bytes.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' {b as char} else {'.'}).collect::<String>();
+    let title = printable(&header[0x20 .. 0x34]).trim_end().to_string();
+    let game_code = printable(&header[0x3B .. 0x3F]);
+    let libultra = libultra_version(header);
+    let cic_type = if rom.len() >= 0x101000 { cic::identify(&rom) } else { None };
+
+    if args.json {
+        println!("{{");
+        println!("  \"title\": {},", json_string(&title));
+        println!("  \"game_code\": {},", json_string(&game_code));
+        println!("  \"region\": \"{}\",", region(header[0x3E]));
+        println!("  \"version\": {},", header[0x3F]);
+        println!("  \"entrypoint\": \"0x{:08X}\",", word(0x08));
+        println!("  \"crc\": [\"0x{:08X}\", \"0x{:08X}\"],", word(0x10), word(0x14));
+        println!("  \"libultra\": {},", libultra.as_deref().map_or(String::from("null"), json_string));
+        println!("  \"cic\": {}", cic_type.as_ref().map_or(String::from("null"), |c| json_string(c.name())));
+        println!("}}");
+    } else {
+        println!("{}", args.path);
+        println!("  title:      {}", title);
+        println!("  game code:  {} ({})", game_code, region(header[0x3E]));
+        println!("  version:    {}", header[0x3F]);
+        println!("  entrypoint: 0x{:08X}", word(0x08));
+        println!("  crc:        {:08X} {:08X}", word(0x10), word(0x14));
+        println!("  libultra:   {}", libultra.as_deref().unwrap_or("unknown"));
+        println!("  cic:        {}", cic_type.as_ref().map_or("unknown", cic::N64CicType::name));
+    }
+    Ok(())
+}
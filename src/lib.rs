@@ -0,0 +1,844 @@
+//rom handling behind bkrom compress and bkrom decompress, for decomp tooling
+//that would rather depend on this crate than shell out to the binaries
+use std::borrow::Cow;
+use std::fs;
+use std::io::Read;
+
+pub mod anti_tamper;
+pub mod cic;
+pub mod error;
+pub mod message;
+pub mod offsets;
+pub mod prelude;
+pub mod profile;
+pub mod rom;
+pub mod store;
+
+pub use error::BkRomError;
+pub use profile::Profile;
+pub use rom::Rom;
+
+//version of the rarezip codec compressed overlays come from, read from its manifest by build.rs
+pub const RAREZIP_VERSION : &str = env!("RAREZIP_VERSION");
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameVersion {
+    USA,
+    PAL,
+    JP,
+    USARevA,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameId {
+    BanjoKazooie(GameVersion),
+}
+
+impl GameId {
+    pub fn version_string(&self) -> &'static str {
+        match self {
+            GameId::BanjoKazooie(GameVersion::USA) => "us_v10",
+            GameId::BanjoKazooie(GameVersion::PAL) => "pal",
+            GameId::BanjoKazooie(GameVersion::JP) => "jp",
+            GameId::BanjoKazooie(GameVersion::USARevA) => "us_v11"
+        }
+    }
+
+    pub fn game_string(&self) -> &'static str {
+        match self {
+            GameId::BanjoKazooie(_) => "banjo",
+        }
+    }
+
+    //identifies a (possibly modified) rom from the product code in its header, e.g. NBKE revision 0
+    pub fn from_header(rom: &[u8]) -> Option<GameId> {
+        match rom.get(0x3B..0x40)? {
+            [b'N', b'B', b'K', b'E', 0] => Some(GameId::BanjoKazooie(GameVersion::USA)),
+            [b'N', b'B', b'K', b'E', 1] => Some(GameId::BanjoKazooie(GameVersion::USARevA)),
+            [b'N', b'B', b'K', b'P', _] => Some(GameId::BanjoKazooie(GameVersion::PAL)),
+            [b'N', b'B', b'K', b'J', _] => Some(GameId::BanjoKazooie(GameVersion::JP)),
+            _ => None,
+        }
+    }
+
+    //standard artifact names used with --out-dir
+    pub fn artifact_stem(&self) -> String {
+        format!("{}.{}", self.game_string(), self.version_string())
+    }
+
+    //fills {game} and {version} in output path templates, e.g. build/{game}.{version}.z64
+    pub fn expand_template(&self, path: &str) -> String {
+        path.replace("{game}", self.game_string()).replace("{version}", self.version_string())
+    }
+}
+
+pub fn find_elf_symbol(symbols: &[Symbol], name: &str)->Result<Symbol, BkRomError>{
+    match symbols.iter().find(|s| {s.name == name}){
+        Some(sym) => Ok(sym.clone()),
+        None => Err(BkRomError::MissingSymbol(name.to_string())),
+    }
+}
+
+//a named address read from the elf
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Symbol {
+    pub name: String,
+    pub value: u64,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Overlay {
+    pub name: String,
+    pub text: std::ops::Range<usize>,
+    pub data: std::ops::Range<usize>,
+    pub bss:  std::ops::Range<usize>,
+    pub uncompressed_rom: std::ops::Range<usize>,
+}
+
+impl Overlay {
+    //text start/end, data start/end, bss start/end, rom start/end
+    pub fn symbol_names(name: &str) -> [String; 8] {
+        [
+            format!("{}_TEXT_START", name),
+            match name {
+                "core1" => format!("{}_DATA_START_OFFSET", name),
+                _ => format!("{}_TEXT_END", name),
+            },
+            match name {
+                "core1" => format!("{}_DATA_START_OFFSET", name),
+                _ => format!("{}_DATA_START", name),
+            },
+            format!("{}_DATA_END", name),
+            format!("{}_BSS_START", name),
+            format!("{}_BSS_END", name),
+            format!("{}_ROM_START", name),
+            format!("{}_ROM_END", name),
+        ]
+    }
+
+    pub fn from_elf_symbols(name: &str, symbols: &[Symbol]) -> Result<Self, BkRomError>{
+        let mut values = [0; 8];
+        for (value, symbol_name) in values.iter_mut().zip(Self::symbol_names(name)) {
+            *value = find_elf_symbol(symbols, &symbol_name)?.value as usize;
+        }
+        let [text_start, text_end, data_start, data_end, bss_start, bss_end, rom_start, rom_end] = values;
+        Ok(Overlay{
+            name: String::from(name),
+            text: text_start..text_end,
+            data: data_start..data_end,
+            bss: bss_start..bss_end,
+            uncompressed_rom: rom_start..rom_end,
+        })
+    }
+}
+
+//reads an elf, transparently inflating gzip compressed (.elf.gz) files
+pub fn read_elf_bytes(path: &str) -> std::io::Result<Vec<u8>> {
+    let elf_bytes = fs::read(path)?;
+    match elf_bytes.get(0..2) {
+        Some([0x1f, 0x8b]) => {
+            let mut inflated = Vec::new();
+            flate2::read::GzDecoder::new(elf_bytes.as_slice()).read_to_end(&mut inflated)?;
+            Ok(inflated)
+        },
+        _ => Ok(elf_bytes),
+    }
+}
+
+//grab all symbols in elf
+pub fn read_elf_symbols(path: &str) -> Result<Vec<Symbol>, BkRomError> {
+    parse_elf_symbols(read_elf_bytes(path).map_err(BkRomError::file(path))?)
+}
+
+//parses elf32 and elf64 files of either endianness from memory, including ones using
+//extended section numbering
+pub fn parse_elf_symbols(elf_bytes: Vec<u8>) -> Result<Vec<Symbol>, BkRomError> {
+    use object::{Object, ObjectSymbol};
+    let elf_file = object::File::parse(elf_bytes.as_slice()).map_err(|e| BkRomError::BadElf(e.to_string()))?;
+    if elf_file.format() != object::BinaryFormat::Elf {
+        return Err(BkRomError::BadElf(format!("expected an elf, found {:?}", elf_file.format())));
+    }
+
+    //only the symbol table is needed, fall back to the dynamic symbols for stripped elfs
+    let symbol_table = match elf_file.symbols().next() {
+        Some(_) => elf_file.symbols(),
+        None => elf_file.dynamic_symbols(),
+    };
+    symbol_table.map(|sym| Ok(Symbol{
+        name: sym.name().map_err(|e| BkRomError::BadElf(e.to_string()))?.to_string(),
+        value: sym.address(),
+    })).collect()
+}
+
+//reads a splat style symbol_addrs.txt
+pub fn read_symbol_addrs(path: &str) -> Result<Vec<Symbol>, BkRomError> {
+    let text = fs::read_to_string(path).map_err(BkRomError::file(path))?;
+    parse_symbol_addrs(&text).map_err(|e| BkRomError::BadSymbol(format!("\"{}\": {}", path, e)))
+}
+
+//name = 0x80000400; lines, anything after // (splat's type:, size:, ... attributes) is ignored
+pub fn parse_symbol_addrs(text: &str) -> Result<Vec<Symbol>, String> {
+    text.lines().enumerate().filter_map(|(i, line)| {
+        let line = line.split("//").next().unwrap().trim();
+        (!line.is_empty()).then_some((i + 1, line))
+    }).map(|(line_number, line)| {
+        let (name, value) = line.strip_suffix(';').and_then(|l| l.split_once('='))
+            .ok_or(format!("line {}: expected name = 0xADDRESS;", line_number))?;
+        let value = value.trim();
+        let value = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => value.parse(),
+        }.map_err(|_| format!("line {}: \"{}\" is not an address", line_number, value))?;
+        Ok(Symbol{name: name.trim().to_string(), value})
+    }).collect()
+}
+
+//symbols from a symbol file replace the elf's symbols of the same name and add any it lacks
+pub fn override_symbols(symbols: &mut Vec<Symbol>, overrides: Vec<Symbol>) {
+    let names : std::collections::HashSet<&str> = overrides.iter().map(|s| s.name.as_str()).collect();
+    symbols.retain(|s| !names.contains(s.name.as_str()));
+    symbols.extend(overrides);
+}
+
+//every symbol compressing reads from the elf that are not in it
+pub fn missing_symbols(symbols: &[Symbol], profile: &Profile, anti_tamper: &[anti_tamper::CrcSlot]) -> Vec<String> {
+    let mut missing : Vec<String> = profile.overlay_names().into_iter().chain(["boot_bk_boot"]).flat_map(Overlay::symbol_names)
+        .chain(anti_tamper.iter().map(|s| s.symbol.clone()))
+        .chain([String::from("crc_ROM_START")])
+        .filter(|name| !symbols.iter().any(|s| &s.name == name))
+        .collect();
+    //core1 uses its data start as text end
+    missing.dedup();
+    missing
+}
+
+//every overlay the elf has a full set of symbols for, from core1 on in uncompressed rom order,
+//so hacks that add or rename overlays don't need a new overlay list
+pub fn discover_overlays(symbols: &[Symbol]) -> Result<Vec<String>, BkRomError> {
+    let core1_start = find_elf_symbol(symbols, "core1_ROM_START")?.value;
+    let mut overlays : Vec<(u64, &str)> = symbols.iter()
+        .filter_map(|s| s.name.strip_suffix("_ROM_START").map(|name| (s.value, name)))
+        .filter(|(rom_start, _)| *rom_start >= core1_start)
+        .filter(|(_, name)| Overlay::symbol_names(name).iter().all(|sym| symbols.iter().any(|s| &s.name == sym)))
+        .collect();
+    overlays.sort();
+    overlays.dedup();
+    Ok(overlays.into_iter().map(|(_, name)| name.to_string()).collect())
+}
+
+pub fn bk_crc(bytes : &[u8]) -> (u32, u32){
+    let crc : (u32, u32) = (0, 0xFFFFFFFF);
+    bytes.iter().fold(crc, |crc, byte| {
+        let a = crc.0 + (*byte as u32); 
+        let b = crc.1 ^ ((*byte as u32) << (a & 0x17));
+        (a, b)
+    })
+}
+
+//the reverse of slice_overlays, puts an uncompressed rom together from the boot.bin and
+//<overlay>.text.bin/.data.bin files decompress --split writes (or a build links separately)
+//at the offsets the elf gives them
+pub fn assemble_split(dir: &str, overlay_offsets: &[Overlay]) -> Result<Vec<u8>, BkRomError> {
+    let read = |name: String| {
+        let path = std::path::Path::new(dir).join(name);
+        fs::read(&path).map_err(BkRomError::file(&path))
+    };
+    let boot = read(String::from("boot.bin"))?;
+    let rom_len = overlay_offsets.iter().map(|x| x.uncompressed_rom.end).fold(boot.len(), usize::max);
+    let mut rom = vec![0; rom_len];
+    rom[..boot.len()].copy_from_slice(&boot);
+    for x in overlay_offsets {
+        let data_start = x.uncompressed_rom.start + x.text.len();
+        for (section, range) in [("text", x.uncompressed_rom.start .. data_start), ("data", data_start .. x.uncompressed_rom.end)] {
+            let bytes = read(format!("{}.{}.bin", x.name, section))?;
+            if bytes.len() != range.len() {
+                return Err(BkRomError::BadRom(format!("{}.{}.bin is 0x{:X} bytes, the elf expects 0x{:X}", x.name, section, bytes.len(), range.len())));
+            }
+            rom[range].copy_from_slice(&bytes);
+        }
+    }
+    Ok(rom)
+}
+
+//splits each overlay in the uncompressed rom into its code and data bytes
+pub fn slice_overlays(overlay_offsets: &[Overlay], uncompressed_rom: &[u8]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>){
+    let uncomp_code_bytes = overlay_offsets.iter().map(|x| {
+        uncompressed_rom[x.uncompressed_rom.start .. x.uncompressed_rom.start + x.text.len()].to_vec()
+    }).collect();
+
+    let uncomp_data_bytes = overlay_offsets.iter().map(|x| {
+        uncompressed_rom[x.uncompressed_rom.start + x.text.len() .. x.uncompressed_rom.end].to_vec()
+    }).collect();
+
+    (uncomp_code_bytes, uncomp_data_bytes)
+}
+
+//rarezip container holding the data as stored (uncompressed) deflate blocks
+fn rzip_stored(bytes: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x11, 0x72];
+    out.extend((bytes.len() as u32).to_be_bytes());
+    let block_count = usize::max(1, bytes.len().div_ceil(0xFFFF));
+    for (i, block) in (0..block_count).map(|i| &bytes[usize::min(i*0xFFFF, bytes.len()) .. usize::min((i+1)*0xFFFF, bytes.len())]).enumerate() {
+        //BFINAL on the last block, BTYPE 00, then LEN and NLEN (little endian)
+        out.push((i + 1 == block_count) as u8);
+        out.extend((block.len() as u16).to_le_bytes());
+        out.extend((!(block.len() as u16)).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out
+}
+
+//offset of an anti-tamper slot inside its overlay's data, None if the elf doesn't have the symbol
+fn slot_offset(symbols: &[Symbol], symbol_name: &str, rom_offset: usize, data_len: usize) -> Result<Option<usize>, BkRomError> {
+    let Some(sym) = symbols.iter().find(|s| s.name == symbol_name) else {
+        return Ok(None);
+    };
+    //anti-tamper slots are words inside the overlay's data
+    (sym.value as usize).checked_sub(rom_offset)
+        .filter(|offset| offset % 4 == 0 && offset + 4 <= data_len)
+        .map(Some)
+        .ok_or_else(|| BkRomError::BadSymbol(format!("{} (0x{:08X}) is not a word inside its overlay's data (0x{:08X}..0x{:08X}), the ROM and ELF may be out of sync", symbol_name, sym.value, rom_offset, rom_offset + data_len)))
+}
+
+//fills each anti-tamper slot, in order, with the crc of its source overlay's code or data
+fn patch_anti_tamper(symbols: &[Symbol], overlay_names: &[&str], overlay_offsets: &[Overlay], code_crcs: &[(u32, u32)], uncomp_data_bytes: &mut [Vec<u8>], anti_tamper: &[anti_tamper::CrcSlot]) -> Result<(), BkRomError> {
+    let replace_symbol = |bytes: &mut Vec<u8>, rom_offset: usize, symbol_name : &str, value : [u8; 4]| -> Result<(), BkRomError>{
+        match slot_offset(symbols, symbol_name, rom_offset, bytes.len())? {
+            Some(offset) => {
+                //expect the zero placeholder or the value already being correct
+                let existing = &bytes[offset .. offset+value.len()];
+                if existing != [0; 4] && existing != value {
+                    message::warning(None, &format!("{} held 0x{:08X} instead of a placeholder, replacing with 0x{:08X}", symbol_name, u32::from_be_bytes(existing.try_into().unwrap()), u32::from_be_bytes(value)));
+                }
+                bytes.splice(offset .. offset+value.len(), value);
+            },
+            None => {message::warning(None, &format!("could not find {} in elf file", symbol_name));}
+        };
+        Ok(())
+    };
+
+    //Replace Overlay CRC's
+    let overlay_index = |name: &str| overlay_names.iter().position(|n| *n == name)
+        .ok_or_else(|| BkRomError::BadSymbol(format!("anti-tamper slot refers to unknown overlay {}", name)));
+    for slot in anti_tamper {
+        let indx = overlay_index(&slot.overlay)?;
+        let source = overlay_index(&slot.source)?;
+        let crc = match slot.section {
+            anti_tamper::Section::Code => code_crcs[source],
+            anti_tamper::Section::Data => {
+                //a data crc stored in its own data is computed with the slot zeroed
+                if source == indx {
+                    replace_symbol(&mut uncomp_data_bytes[indx], overlay_offsets[indx].data.start, &slot.symbol, [0;4])?;
+                }
+                bk_crc(&uncomp_data_bytes[source])
+            },
+        };
+        let value = if slot.word == 0 {crc.0} else {crc.1};
+        replace_symbol(&mut uncomp_data_bytes[indx], overlay_offsets[indx].data.start, &slot.symbol, value.to_be_bytes())?;
+    }
+    Ok(())
+}
+
+//code crc pair and data crc pair of one overlay
+pub type OverlayCrcs = ((u32, u32), (u32, u32));
+
+//what compress_overlays produced for each overlay, in ram order
+pub struct CompressedOverlays {
+    pub rzip_bytes: Vec<Vec<u8>>,
+    //code and (patched) data crcs
+    pub crcs: Vec<OverlayCrcs>,
+    //(overlay, "code" or "data") of every section stored uncompressed because compressing grew it
+    pub stored: Vec<(String, &'static str)>,
+}
+
+//called with the overlay names and the code and data of each overlay, in ram order
+pub type SectionCallback<'a> = Box<dyn FnMut(&[&str], &mut [Vec<u8>], &mut [Vec<u8>]) -> Result<(), BkRomError> + 'a>;
+//called with the laid out rom
+pub type RomCallback<'a> = Box<dyn FnMut(&mut Rom<'_>) -> Result<(), BkRomError> + 'a>;
+
+//caller supplied steps run at fixed points of compress_rom_with, e.g. to inject cheats without
+//forking the pipeline, each may inspect or change the buffers or abort the build with an error
+#[derive(Default)]
+pub struct Callbacks<'a> {
+    //right after the overlays are sliced out of the uncompressed rom, before any crc is taken
+    pub after_slice: Option<SectionCallback<'a>>,
+    //after the anti-tamper slots are filled, code crcs are already in the slots by then but
+    //the data crcs of the rom header are taken afterwards
+    pub after_anti_tamper: Option<SectionCallback<'a>>,
+    //once the rom is laid out, before it is written, e.g. to add header_patches
+    pub before_write: Option<RomCallback<'a>>,
+}
+
+//patches the anti-tamper crcs and compresses each overlay
+pub fn compress_overlays(symbols: &[Symbol], overlay_names: &[&str], overlay_offsets: &[Overlay], uncompressed_rom: &[u8], anti_tamper: &[anti_tamper::CrcSlot], store: Option<&store::Store>, self_check: bool) -> Result<CompressedOverlays, BkRomError>{
+    compress_overlays_with(symbols, overlay_names, overlay_offsets, uncompressed_rom, anti_tamper, store, self_check, &mut Callbacks::default())
+}
+
+//compress_overlays running the after_slice and after_anti_tamper callbacks
+#[allow(clippy::too_many_arguments)]
+pub fn compress_overlays_with(symbols: &[Symbol], overlay_names: &[&str], overlay_offsets: &[Overlay], uncompressed_rom: &[u8], anti_tamper: &[anti_tamper::CrcSlot], store: Option<&store::Store>, self_check: bool, callbacks: &mut Callbacks) -> Result<CompressedOverlays, BkRomError>{
+    //seperate bits
+    let (mut uncomp_code_bytes, mut uncomp_data_bytes) = slice_overlays(overlay_offsets, uncompressed_rom);
+    if let Some(after_slice) = &mut callbacks.after_slice {
+        after_slice(overlay_names, &mut uncomp_code_bytes, &mut uncomp_data_bytes)?;
+    }
+
+    log::debug!("Calculating Overlay CRCs...");
+    let code_crcs :Vec<_>= uncomp_code_bytes.iter().map(|c_bytes| { bk_crc(c_bytes) }).collect();
+    for (name, crc) in overlay_names.iter().zip(&code_crcs){
+        log::debug!("{} (0x{:08X?}, 0x{:08X?})", name, crc.0, crc.1);
+    }
+
+    patch_anti_tamper(symbols, overlay_names, overlay_offsets, &code_crcs, &mut uncomp_data_bytes, anti_tamper)?;
+    if let Some(after_anti_tamper) = &mut callbacks.after_anti_tamper {
+        after_anti_tamper(overlay_names, &mut uncomp_code_bytes, &mut uncomp_data_bytes)?;
+    }
+
+    let crcs = code_crcs.into_iter().zip(uncomp_data_bytes.iter().map(|d_bytes| bk_crc(d_bytes))).collect();
+
+    log::debug!("Compressing Overlays...");
+    let zip = |bytes: &[u8]| match store {
+        //a cached blob, possibly from a shared cache, is only used if it decompresses back to the input
+        Some(store) => store.get_or_insert_with("bk_zip", bytes, |rzip| offsets::rzip_block_len(rzip, 0).is_some() && rarezip::bk::unzip(rzip) == bytes, || rarezip::bk::zip(bytes)),
+        None => rarezip::bk::zip(bytes),
+    };
+    //decompress each blob again and compare against its input
+    let check = |name: &str, section: &str, bytes: &[u8], rzip: &[u8]| {
+        if self_check && rarezip::bk::unzip(rzip) != bytes {
+            return Err(BkRomError::SelfCheckFailed{overlay: name.to_string(), section: section.to_string()});
+        }
+        Ok(())
+    };
+    //fall back to stored deflate blocks when compressing would grow the data
+    let stored_sections = std::cell::RefCell::new(Vec::new());
+    let zip_or_store = |name: &str, section: &'static str, bytes: &[u8]| {
+        let rzip = zip(bytes);
+        let stored = rzip_stored(bytes);
+        if stored.len() < rzip.len() {
+            log::info!("notice: {} {} does not compress (0x{:X} > 0x{:X} bytes), storing it uncompressed", name, section, rzip.len(), stored.len());
+            stored_sections.borrow_mut().push((name.to_string(), section));
+            return stored
+        }
+        rzip
+    };
+    let rzip_bytes : Vec<Vec<u8>> = overlay_names.iter().zip(uncomp_code_bytes.into_iter().zip(uncomp_data_bytes)).enumerate().map(|(i, (name, (code, data)))| {
+        let mut code_rzip = zip_or_store(name, "code", &code);
+        let mut data_rzip = zip_or_store(name, "data", &data);
+        check(name, "code", &code, &code_rzip)?;
+        check(name, "data", &data, &data_rzip)?;
+        code_rzip.append(&mut data_rzip);
+        code_rzip.resize(code_rzip.len().next_multiple_of(16), 0);
+        message::progress(name, i + 1, overlay_names.len());
+        Ok(code_rzip)
+    }).collect::<Result<_, BkRomError>>()?;
+
+    Ok(CompressedOverlays{rzip_bytes, crcs, stored: stored_sections.into_inner()})
+}
+
+//compresses every overlay and lays them out after the boot segments of the uncompressed rom
+pub fn compress_rom<'a>(symbols: &'a [Symbol], uncompressed_rom: &'a [u8], profile: &'a Profile, anti_tamper: &[anti_tamper::CrcSlot], store: Option<&store::Store>, self_check: bool) -> Result<Rom<'a>, BkRomError> {
+    compress_rom_with(symbols, uncompressed_rom, profile, anti_tamper, store, self_check, Callbacks::default())
+}
+
+//compress_rom running the given callbacks along the way
+pub fn compress_rom_with<'a>(symbols: &'a [Symbol], uncompressed_rom: &'a [u8], profile: &'a Profile, anti_tamper: &[anti_tamper::CrcSlot], store: Option<&store::Store>, self_check: bool, mut callbacks: Callbacks) -> Result<Rom<'a>, BkRomError> {
+    let bk_boot_info = Overlay::from_elf_symbols("boot_bk_boot", symbols)?;
+    let bk_boot_bytes = uncompressed_rom.get(bk_boot_info.uncompressed_rom.clone())
+        .ok_or(BkRomError::BadRom(String::from("uncompressed rom is shorter than the elf's bk_boot segment")))?
+        .to_vec();
+
+    //overlays offsets from elf symbols
+    let mut overlay_names = profile.overlay_names();
+    let mut overlay_offsets : Vec<Overlay> = overlay_names.iter().map(|ovrly_name| {Overlay::from_elf_symbols(ovrly_name, symbols)}).collect::<Result<_, _>>()?;
+    if let Some(ovrly) = overlay_offsets.iter().find(|ovrly| ovrly.uncompressed_rom.end > uncompressed_rom.len()) {
+        return Err(BkRomError::BadRom(format!("uncompressed rom is shorter than the elf's {} segment", ovrly.name)));
+    }
+
+    let CompressedOverlays{mut rzip_bytes, crcs: mut overlay_crcs, stored} = compress_overlays_with(symbols, &overlay_names, &overlay_offsets, uncompressed_rom, anti_tamper, store, self_check, &mut callbacks)?;
+    let overlay_start_offset = overlay_offsets[0].uncompressed_rom.start;
+    let (core1_code_crc, core1_data_crc) = overlay_crcs[overlay_names.iter().position(|n| *n == "core1").ok_or(BkRomError::MissingOverlay(String::from("core1")))?];
+
+    //e.g. GV and MMM
+    profile.swap_rom_order(&mut overlay_names);
+    profile.swap_rom_order(&mut overlay_offsets);
+    profile.swap_rom_order(&mut rzip_bytes);
+    profile.swap_rom_order(&mut overlay_crcs);
+
+    let mut rom = Rom{
+        uncompressed_rom,
+        symbols,
+        bk_boot_info,
+        bk_boot_bytes,
+        core1_code_crc,
+        core1_data_crc,
+        overlay_names,
+        overlay_offsets,
+        overlay_crcs,
+        rzip_bytes,
+        stored,
+        overlay_start_offset,
+        pad_to: Some(rom::ROM_SIZE),
+        fill: 0xFF,
+        slots: None,
+        header_patches: Vec::new(),
+    };
+    if let Some(before_write) = &mut callbacks.before_write {
+        before_write(&mut rom)?;
+    }
+    Ok(rom)
+}
+
+//byte order is fixed in place, roms are too big to keep a second copy around
+fn le_to_me(le_buff : &mut [u8]) {
+    le_buff.chunks_exact_mut(2).for_each(|a| a.reverse());
+}
+
+fn le_to_be(le_buff : &mut [u8]) {
+    le_buff.chunks_exact_mut(4).for_each(|a| a.reverse());
+}
+
+pub fn rom_to_big_endian(mut rom_bin : Vec<u8>) -> Result<Vec<u8>, BkRomError> {
+    let signature = rom_bin.get(0..4).unwrap_or_default();
+    match signature {
+        [0x80, 0x37, 0x12, 0x40] => {},
+        [0x40, 0x12, 0x37, 0x80] => {le_to_be(&mut rom_bin)},
+        [0x37, 0x80, 0x40, 0x12] => {le_to_me(&mut rom_bin)},
+        _ => return Err(BkRomError::BadRom(String::from("not an n64 rom (unknown byte order signature)"))),
+    }
+    Ok(rom_bin)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteOrder {
+    //.z64
+    BigEndian,
+    //.v64
+    ByteSwapped,
+    //.n64
+    LittleEndian,
+}
+
+impl ByteOrder {
+    pub fn detect(rom_bin : &[u8]) -> Option<ByteOrder> {
+        match rom_bin.get(0..4)? {
+            [0x80, 0x37, 0x12, 0x40] => Some(ByteOrder::BigEndian),
+            [0x37, 0x80, 0x40, 0x12] => Some(ByteOrder::ByteSwapped),
+            [0x40, 0x12, 0x37, 0x80] => Some(ByteOrder::LittleEndian),
+            _ => None,
+        }
+    }
+}
+
+//converts a big endian rom to the given byte order in place, for tools that expect .v64/.n64
+pub fn rom_from_big_endian(rom_bin : &mut [u8], order : ByteOrder) {
+    match order {
+        ByteOrder::BigEndian => {},
+        ByteOrder::ByteSwapped => le_to_me(rom_bin),
+        ByteOrder::LittleEndian => le_to_be(rom_bin),
+    }
+}
+
+//borrows a rom that is already big endian, otherwise returns a converted copy
+pub fn as_big_endian(rom_bin : &[u8]) -> Result<Cow<'_, [u8]>, BkRomError> {
+    match rom_bin.get(0..4) {
+        Some([0x80, 0x37, 0x12, 0x40]) => Ok(Cow::Borrowed(rom_bin)),
+        _ => rom_to_big_endian(rom_bin.to_vec()).map(Cow::Owned),
+    }
+}
+
+//maps a rom read only instead of reading it into memory
+pub fn map_rom(path: &str) -> Result<memmap2::Mmap, BkRomError> {
+    let file = fs::File::open(path).map_err(BkRomError::file(path))?;
+    //safety: nothing else is expected to modify the rom while it is being read
+    unsafe { memmap2::Mmap::map(&file) }.map_err(BkRomError::file(path))
+}
+
+pub const N64_SIGNATURES : [[u8; 4]; 3] = [[0x80, 0x37, 0x12, 0x40], [0x40, 0x12, 0x37, 0x80], [0x37, 0x80, 0x40, 0x12]];
+
+//some re-releases (e.g. Wii VC extracts) and copier dumps prepend a small header
+//returns the rom with the header removed along with the stripped length
+pub fn strip_wrapper_header(mut rom_bin : Vec<u8>) -> (Vec<u8>, usize) {
+    if rom_bin.len() < 4 || N64_SIGNATURES.contains(&rom_bin[0..4].try_into().unwrap()) {
+        return (rom_bin, 0);
+    }
+    let header_len = (0x10..usize::min(0x10000, rom_bin.len().saturating_sub(0x101000)))
+        .step_by(0x10)
+        .find(|offset| {N64_SIGNATURES.contains(&rom_bin[*offset .. *offset + 4].try_into().unwrap())});
+    match header_len {
+        Some(len) => {
+            rom_bin.drain(..len);
+            (rom_bin, len)
+        },
+        None => (rom_bin, 0),
+    }
+}
+
+//rarezip blocks start with 0x1172 followed by the big endian uncompressed length
+pub fn rzip_uncompressed_size(rzip : &[u8]) -> Result<usize, BkRomError> {
+    match rzip.get(0..6) {
+        Some([0x11, 0x72, size @ ..]) => Ok(u32::from_be_bytes(size.try_into().unwrap()) as usize),
+        _ => Err(BkRomError::BadRom(String::from("Invalid rarezip header"))),
+    }
+}
+
+//compressed blocks (code then data for each overlay) in uncompressed rom order,
+//where each one decompresses to and the length of the decompressed rom
+pub type DecompressionLayout<'a> = (Vec<&'a [u8]>, Vec<usize>, usize);
+
+pub fn decompression_layout<'a>(compressed_rom : &'a [u8], file_offsets : &[usize], profile : &Profile) -> Result<DecompressionLayout<'a>, BkRomError> {
+    //slice rom into (code, data) pairs
+    let mut compressed_overlays : Vec<[&[u8]; 2]> = file_offsets.windows(2)
+        .map(|w| compressed_rom.get(w[0]..w[1]).ok_or_else(|| BkRomError::BadRom(format!("overlay block 0x{:X}..0x{:X} is not inside the 0x{:X} byte rom", w[0], w[1], compressed_rom.len()))))
+        .collect::<Result<Vec<_>, _>>()?
+        .chunks_exact(2)
+        .map(|pair| [pair[0], pair[1]])
+        .collect();
+    profile.swap_rom_order(&mut compressed_overlays);
+    let compressed_overlays : Vec<&[u8]> = compressed_overlays.into_iter().flatten().collect();
+
+    //final offsets from the uncompressed sizes in each rarezip header
+    let mut overlay_offsets : Vec<usize> = Vec::with_capacity(compressed_overlays.len());
+    let mut rom_len = file_offsets[0];
+    for ovrly in &compressed_overlays {
+        overlay_offsets.push(rom_len);
+        rom_len += rzip_uncompressed_size(ovrly)?;
+    }
+    Ok((compressed_overlays, overlay_offsets, rom_len))
+}
+
+//decompresses a freshly built rom again and compares it against the uncompressed input,
+//skipping the words compressing is meant to change (header crc, crc block, anti-tamper slots)
+pub fn verify_round_trip(compressed_rom : &[u8], uncompressed_rom : &[u8], profile : &Profile, symbols : &[Symbol], anti_tamper : &[anti_tamper::CrcSlot]) -> Result<(), BkRomError> {
+    let decompressed = decompress_rom(compressed_rom, profile)?;
+
+    let crc_rom_start = find_elf_symbol(symbols, "crc_ROM_START")?.value as usize;
+    let mut patched = vec![0x10..0x18, crc_rom_start..crc_rom_start + 0x20];
+    for slot in anti_tamper {
+        let ovrly = Overlay::from_elf_symbols(&slot.overlay, symbols)?;
+        if let Some(data_offset) = slot_offset(symbols, &slot.symbol, ovrly.data.start, ovrly.data.len())? {
+            let offset = ovrly.uncompressed_rom.start + ovrly.text.len() + data_offset;
+            patched.push(offset .. offset + 4);
+        }
+    }
+
+    let overlays_end = profile.overlays.iter()
+        .map(|name| Overlay::from_elf_symbols(name, symbols).map(|ovrly| ovrly.uncompressed_rom.end))
+        .try_fold(0, |end, ovrly_end| ovrly_end.map(|e| usize::max(end, e)))?;
+    let first_difference = (0..overlays_end).find(|i| {
+        decompressed.get(*i) != uncompressed_rom.get(*i) && !patched.iter().any(|range| range.contains(i))
+    });
+    match first_difference {
+        Some(offset) => Err(BkRomError::RoundTripMismatch(offset)),
+        None => Ok(()),
+    }
+}
+
+//decompresses a big endian retail (or modified) rom
+pub fn decompress_rom(compressed_rom : &[u8], profile : &Profile) -> Result<Vec<u8>, BkRomError> {
+    let overlay_offsets = offsets::discover(compressed_rom, profile)
+        .ok_or_else(|| BkRomError::BadRom(format!("file offsets not found for {:?}", profile.game_id)))?;
+    log::info!("overlay offsets from {:?} (confidence: {:?})", overlay_offsets.source, overlay_offsets.confidence);
+    let file_offsets = overlay_offsets.file_offsets;
+    let (compressed_overlays, overlay_offsets, rom_len) = decompression_layout(compressed_rom, &file_offsets, profile)?;
+    let mut rom = vec![0; rom_len];
+    rom[..file_offsets[0]].copy_from_slice(&compressed_rom[..file_offsets[0]]);
+    for (ovrly, offset) in compressed_overlays.iter().zip(overlay_offsets) {
+        let bytes = rarezip::bk::unzip(ovrly);
+        if bytes.len() != rzip_uncompressed_size(ovrly)? {
+            return Err(BkRomError::BadRom(String::from("Decompressed overlay size does not match rarezip header")));
+        }
+        rom[offset .. offset + bytes.len()].copy_from_slice(&bytes);
+    }
+    Ok(rom)
+}
+
+//an anti-tamper value of a built rom, what it holds and what the game will compute
+#[derive(Debug)]
+pub struct TamperCheck {
+    pub name: String,
+    pub stored: u32,
+    pub expected: u32,
+}
+
+//recomputes every anti-tamper slot and the boot crc block of a decompressed rom the same way
+//compressing fills them, a mismatch crashes the game once it checks that value
+pub fn check_anti_tamper(decompressed : &[u8], symbols : &[Symbol], profile : &Profile, anti_tamper : &[anti_tamper::CrcSlot]) -> Result<Vec<TamperCheck>, BkRomError> {
+    let overlay_names = profile.overlay_names();
+    let overlay_offsets : Vec<Overlay> = overlay_names.iter().map(|name| Overlay::from_elf_symbols(name, symbols)).collect::<Result<_, _>>()?;
+    if let Some(ovrly) = overlay_offsets.iter().find(|ovrly| ovrly.uncompressed_rom.end > decompressed.len()) {
+        return Err(BkRomError::BadRom(format!("decompressed rom is shorter than the elf's {} segment", ovrly.name)));
+    }
+    let (code_bytes, mut data_bytes) = slice_overlays(&overlay_offsets, decompressed);
+    let code_crcs : Vec<_> = code_bytes.iter().map(|c_bytes| bk_crc(c_bytes)).collect();
+
+    //read the stored values, then zero them again like the placeholders compress starts from
+    let overlay_index = |name: &str| overlay_names.iter().position(|n| *n == name)
+        .ok_or_else(|| BkRomError::BadSymbol(format!("anti-tamper slot refers to unknown overlay {}", name)));
+    let word = |bytes: &[u8], offset: usize| u32::from_be_bytes(bytes[offset .. offset + 4].try_into().unwrap());
+    let mut slots = Vec::new();
+    for slot in anti_tamper {
+        let indx = overlay_index(&slot.overlay)?;
+        let offset = slot_offset(symbols, &slot.symbol, overlay_offsets[indx].data.start, data_bytes[indx].len())?
+            .ok_or_else(|| BkRomError::BadSymbol(format!("could not find {} in elf file", slot.symbol)))?;
+        slots.push((slot, indx, offset, word(&data_bytes[indx], offset)));
+        data_bytes[indx][offset .. offset + 4].fill(0);
+    }
+    patch_anti_tamper(symbols, &overlay_names, &overlay_offsets, &code_crcs, &mut data_bytes, anti_tamper)?;
+
+    let mut checks : Vec<TamperCheck> = slots.into_iter().map(|(slot, indx, offset, stored)| TamperCheck{
+        name: format!("{} ({} {} {} word {})", slot.symbol, slot.overlay, slot.source, match slot.section {anti_tamper::Section::Code => "code", anti_tamper::Section::Data => "data"}, slot.word),
+        stored,
+        expected: word(&data_bytes[indx], offset),
+    }).collect();
+
+    //bk_boot and core1 crcs the boot code checks
+    let bk_boot_info = Overlay::from_elf_symbols("boot_bk_boot", symbols)?;
+    let bk_boot_bytes = decompressed.get(bk_boot_info.uncompressed_rom.clone())
+        .ok_or(BkRomError::BadRom(String::from("decompressed rom is shorter than the elf's bk_boot segment")))?;
+    let core1 = overlay_index("core1")?;
+    let crc_rom_start = find_elf_symbol(symbols, "crc_ROM_START")?.value as usize;
+    let crc_block = decompressed.get(crc_rom_start .. crc_rom_start + 0x18)
+        .ok_or(BkRomError::BadRom(format!("crc_ROM_START (0x{:X}) is past the end of the rom", crc_rom_start)))?;
+    let expected = [("bk_boot", bk_crc(bk_boot_bytes)), ("core1 code", code_crcs[core1]), ("core1 data", bk_crc(&data_bytes[core1]))];
+    for (i, (name, crc)) in expected.into_iter().enumerate() {
+        checks.push(TamperCheck{name: format!("crc_ROM_START {} word 0", name), stored: word(crc_block, 8*i), expected: crc.0});
+        checks.push(TamperCheck{name: format!("crc_ROM_START {} word 1", name), stored: word(crc_block, 8*i + 4), expected: crc.1});
+    }
+    Ok(checks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, value: u64) -> Symbol {
+        Symbol{name: name.to_string(), value}
+    }
+
+    fn overlay(name: &str, data_start: usize) -> Overlay {
+        Overlay{name: name.to_string(), text: 0..0, data: data_start..data_start + 0x10, bss: 0..0, uncompressed_rom: 0..0}
+    }
+
+    #[test]
+    fn rzip_header() {
+        assert_eq!(rzip_uncompressed_size(&[0x11, 0x72, 0x00, 0x01, 0x23, 0x40, 0xAA]).unwrap(), 0x12340);
+        assert!(rzip_uncompressed_size(&[0x11, 0x73, 0, 0, 0, 0]).is_err());
+        assert!(rzip_uncompressed_size(&[0x11, 0x72, 0]).is_err());
+    }
+
+    #[test]
+    fn stored_rzip_round_trip() {
+        //more than one 0xFFFF byte stored block
+        let bytes : Vec<u8> = (0..0x20000u32).map(|i| (i*13 % 256) as u8).collect();
+        for data in [&bytes[..], &bytes[..5], &[]] {
+            let stored = rzip_stored(data);
+            assert_eq!(rzip_uncompressed_size(&stored).unwrap(), data.len());
+            assert_eq!(rarezip::bk::unzip(&stored), data);
+        }
+    }
+
+    #[test]
+    fn rom_order_swap() {
+        let profile = profile::find("us.v10").unwrap();
+        let mut names = profile.overlay_names();
+        profile.swap_rom_order(&mut names);
+        assert_eq!(names[3..5], ["MMM", "GV"]);
+        //the swaps are their own inverse
+        profile.swap_rom_order(&mut names);
+        assert_eq!(names, profile.overlay_names());
+    }
+
+    #[test]
+    fn crc_slot_patching() {
+        let slots = anti_tamper::parse(concat!(
+            "slot = [{ symbol = \"D_A\", overlay = \"b\", source = \"a\", section = \"code\", word = 1 },\n",
+            "        { symbol = \"D_B\", overlay = \"b\", source = \"b\", section = \"data\", word = 0 }]\n")).unwrap();
+        let symbols = [symbol("D_A", 0x1004), symbol("D_B", 0x1008)];
+        let overlays = [overlay("a", 0x0), overlay("b", 0x1000)];
+        let code_crcs = [(0x11111111, 0x22222222), (0x33333333, 0x44444444)];
+        let mut data = vec![vec![0; 0x10], vec![0xAB; 0x10]];
+        data[1][4..12].fill(0);
+        patch_anti_tamper(&symbols, &["a", "b"], &overlays, &code_crcs, &mut data, &slots).unwrap();
+        assert_eq!(data[1][4..8], 0x22222222u32.to_be_bytes());
+        //a data crc stored in its own data is taken with the slot zeroed
+        let mut zeroed = data[1].clone();
+        zeroed[8..12].fill(0);
+        assert_eq!(data[1][8..12], bk_crc(&zeroed).0.to_be_bytes());
+        assert_eq!(data[1][..4], [0xAB; 4]);
+    }
+
+    #[test]
+    fn crc_slot_outside_data() {
+        let slots = anti_tamper::parse("slot = [{ symbol = \"D_A\", overlay = \"a\", source = \"a\", section = \"code\", word = 0 }]\n").unwrap();
+        let mut data = vec![vec![0; 0x10]];
+        let result = patch_anti_tamper(&[symbol("D_A", 0x20)], &["a"], &[overlay("a", 0)], &[(0, 0)], &mut data, &slots);
+        assert!(matches!(result, Err(BkRomError::BadSymbol(_))));
+    }
+
+    #[test]
+    fn callbacks() {
+        let overlays = [Overlay{name: "a".to_string(), text: 0x100..0x108, data: 0x108..0x110, bss: 0..0, uncompressed_rom: 0..0x10}];
+        let slots = anti_tamper::parse("slot = [{ symbol = \"D_A\", overlay = \"a\", source = \"a\", section = \"code\", word = 0 }]\n").unwrap();
+        let mut callbacks = Callbacks{
+            after_slice: Some(Box::new(|names, code, _| {
+                assert_eq!(names, ["a"]);
+                code[0][0] = 0xAA;
+                Ok(())
+            })),
+            after_anti_tamper: Some(Box::new(|_, code, data| {
+                //the slot holds the crc of the patched code
+                assert_eq!(data[0][..4], bk_crc(&code[0]).0.to_be_bytes());
+                data[0][7] = 0xBB;
+                Ok(())
+            })),
+            before_write: None,
+        };
+        let compressed = compress_overlays_with(&[symbol("D_A", 0x108)], &["a"], &overlays, &[0; 0x10], &slots, None, true, &mut callbacks).unwrap();
+        let code = [0xAA, 0, 0, 0, 0, 0, 0, 0];
+        let mut data = bk_crc(&code).0.to_be_bytes().to_vec();
+        data.extend([0, 0, 0, 0xBB]);
+        assert_eq!(compressed.crcs, [(bk_crc(&code), bk_crc(&data))]);
+
+        let mut callbacks = Callbacks{after_slice: Some(Box::new(|_, _, _| Err(BkRomError::BadPatch(String::from("no"))))), ..Default::default()};
+        assert!(compress_overlays_with(&[], &["a"], &overlays, &[0; 0x10], &[], None, false, &mut callbacks).is_err());
+    }
+
+    #[test]
+    fn byte_orders() {
+        let z64 = vec![0x80, 0x37, 0x12, 0x40, 1, 2, 3, 4];
+        for order in [ByteOrder::ByteSwapped, ByteOrder::LittleEndian] {
+            let mut converted = z64.clone();
+            rom_from_big_endian(&mut converted, order);
+            assert_eq!(ByteOrder::detect(&converted), Some(order));
+            assert_eq!(rom_to_big_endian(converted).unwrap(), z64);
+        }
+        assert!(rom_to_big_endian(vec![0; 8]).is_err());
+    }
+
+    #[test]
+    fn profile_toml() {
+        let def = concat!("version = \"pal\"\ncic = \"6103\"\noverlays = [\"core1\", \"core2\"]\n",
+            "rom_order_swaps = [[\"core1\", \"core2\"]]\noverlay_offsets = [0x100, 0x180, 0x200, 0x280, 0x300]\n");
+        let profile = profile::parse(def).unwrap();
+        assert_eq!(profile.cli_name, "pal");
+        assert_eq!(profile.rom_order_swaps, [(0, 1)]);
+        assert_eq!(profile.overlay_slots().unwrap(), [0x100..0x200, 0x200..0x300]);
+        assert!(profile.compressed_md5.is_none() && profile.asset_table.is_none());
+        assert!(profile.anti_tamper_slots().is_err());
+
+        assert!(profile::parse(&def.replace("0x280, ", "")).is_err());
+        assert!(profile::parse(&def.replace("\"pal\"", "\"us.v12\"")).is_err());
+        assert!(profile::parse(&def.replace("[[\"core1\", \"core2\"]]", "[[\"core1\", \"core3\"]]")).is_err());
+    }
+
+    #[test]
+    fn builtin_profiles() {
+        assert_eq!(profile::PROFILES.len(), 4);
+        assert!(profile::find("us.v10").unwrap().anti_tamper_slots().is_ok());
+        assert_eq!(profile::find("us.v10").unwrap().asset_table, Some(0x10CD0));
+    }
+}
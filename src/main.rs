@@ -0,0 +1,134 @@
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
+use std::io::Write;
+use bk_rom_comp::message;
+
+mod analyze;
+mod assets;
+mod comp;
+mod crcfix;
+mod dat;
+mod decomp;
+mod hook;
+mod identify;
+mod info;
+mod patch;
+mod progress;
+mod prompt;
+mod rzip;
+mod size_diff;
+mod stats;
+mod texture;
+mod verify_antitamper;
+mod version;
+
+/// bkrom compresses and decompresses the overlays of the BanjoKazooie rom
+#[derive(Parser, Debug)]
+#[command(name = "bkrom", version = version::TOOL_VERSION)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    /// prints each step and the crcs being patched (same as BKROM_LOG=debug)
+    #[arg(long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// only prints warnings and errors, no notices or progress bars (same as
+    /// BKROM_LOG=warn)
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// sets how warnings and errors are printed, github prints workflow commands
+    /// (::warning ...) so problems show up inline in pull request checks,
+    /// annotations prints "<file>: <level>: <message>"
+    #[arg(long, global = true, default_value = "human",
+        value_parser = PossibleValuesParser::new(message::MessageFormat::NAMES).try_map(|s| s.parse::<message::MessageFormat>()))]
+    message_format: message::MessageFormat,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// compresses all the overlays into the BanjoKazooie rom
+    #[command(after_help = comp::AFTER_HELP)]
+    Compress(Box<comp::Args>),
+    /// decompresses all the overlays in BanjoKazooie
+    #[command(after_help = decomp::AFTER_HELP)]
+    Decompress(decomp::Args),
+    /// prints each overlay's entropy and an estimated compressed size without
+    /// running the encoder, to check whether a change will still fit
+    Analyze(analyze::Args),
+    /// compresses two builds and prints the compressed size change of every overlay,
+    /// e.g. to post the rom size impact of a pull request
+    SizeDiff(size_diff::Args),
+    /// summarizes a directory of build reports (compress --report): each overlay's
+    /// compressed size over time and the builds where it grew
+    Stats(stats::Args),
+    /// identifies the CIC of any n64 rom and rewrites its header crc in place
+    #[command(after_help = crcfix::AFTER_HELP)]
+    Crcfix(crcfix::Args),
+    /// prints the byte order, hashes, version, CIC and layout of a rom
+    Identify(identify::Args),
+    /// prints the header fields of any n64 rom (title, game code, entrypoint, libultra, CIC)
+    Info(info::Args),
+    /// compresses any file with the rarezip bk format the overlays use
+    Zip(rzip::ZipArgs),
+    /// decompresses a single rarezip bk blob
+    Unzip(rzip::UnzipArgs),
+    /// extracts, repacks and decodes the assets of a compressed rom
+    #[command(after_help = assets::AFTER_HELP)]
+    Assets(assets::Args),
+    /// recomputes the anti-tamper crcs of a compressed rom and reports any that don't
+    /// match, which would crash the game on console
+    VerifyAntitamper(verify_antitamper::Args),
+}
+
+//reports a command line problem the way clap does, with the subcommand's usage
+pub fn usage_error(subcommand: &str, kind: ErrorKind, msg: &str) -> ! {
+    let mut cli = Cli::command();
+    cli.build();
+    cli.find_subcommand_mut(subcommand).expect("unknown subcommand").error(kind, msg).exit()
+}
+
+//notices and warnings go to stderr, leaving stdout for reports and json output
+//BKROM_LOG (e.g. BKROM_LOG=debug or BKROM_LOG=bk_rom_comp=debug) overrides the flags
+fn init_logging(verbose: bool, quiet: bool) {
+    let level = match (verbose, quiet) {
+        (true, _) => log::LevelFilter::Debug,
+        (_, true) => log::LevelFilter::Warn,
+        _ => log::LevelFilter::Info,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .parse_env("BKROM_LOG")
+        .format(|buf, record| match record.level() {
+            log::Level::Info => writeln!(buf, "{}", record.args()),
+            log::Level::Warn => writeln!(buf, "warning: {}", record.args()),
+            level => writeln!(buf, "{}: {}", level.as_str().to_lowercase(), record.args()),
+        })
+        .init();
+}
+
+fn main() {
+    let cli = Cli::parse();
+    init_logging(cli.verbose, cli.quiet);
+    message::set_format(cli.message_format);
+    let result = match cli.command {
+        Command::Compress(args) => {progress::install("compressing"); comp::run(*args)},
+        Command::Decompress(args) => decomp::run(args),
+        Command::Analyze(args) => analyze::run(args),
+        Command::SizeDiff(args) => size_diff::run(args),
+        Command::Stats(args) => stats::run(args),
+        Command::Crcfix(args) => crcfix::run(args),
+        Command::Identify(args) => identify::run(args),
+        Command::Info(args) => info::run(args),
+        Command::Zip(args) => rzip::zip(args),
+        Command::Unzip(args) => rzip::unzip(args),
+        Command::Assets(args) => assets::run(args),
+        Command::VerifyAntitamper(args) => verify_antitamper::run(args),
+    };
+    if let Err(e) = result {
+        message::error(None, &e.to_string());
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,79 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageFormat {
+    Human,
+    Github,
+    Annotations,
+}
+
+static FORMAT : OnceLock<MessageFormat> = OnceLock::new();
+
+type ProgressHook = Box<dyn Fn(&str, usize, usize) + Send + Sync>;
+static PROGRESS : OnceLock<ProgressHook> = OnceLock::new();
+
+impl MessageFormat {
+    pub const NAMES : [&'static str; 3] = ["human", "github", "annotations"];
+}
+
+impl FromStr for MessageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "github" => Ok(MessageFormat::Github),
+            "annotations" => Ok(MessageFormat::Annotations),
+            _ => Err(format!("unknown message format \"{}\", expected one of {}", s, MessageFormat::NAMES.join(", "))),
+        }
+    }
+}
+
+//selects how warnings and errors are printed
+pub fn set_format(format: MessageFormat) {
+    FORMAT.set(format).expect("message format already set");
+}
+
+//receives (item, done, total) after each step of the long running loops, e.g. each compressed overlay
+pub fn set_progress_hook(hook: ProgressHook) {
+    PROGRESS.set(hook).ok().expect("progress hook already set");
+}
+
+pub fn progress(item: &str, done: usize, total: usize) {
+    if let Some(hook) = PROGRESS.get() {
+        hook(item, done, total);
+    }
+}
+
+pub fn warning(file: Option<&str>, msg: &str) {
+    emit("warning", file, msg);
+}
+
+//for reporting errors without aborting
+pub fn error(file: Option<&str>, msg: &str) {
+    emit("error", file, msg);
+}
+
+//on stderr like the human format, stdout is kept for reports and json output
+fn emit(level: &str, file: Option<&str>, msg: &str) {
+    match (FORMAT.get().copied().unwrap_or(MessageFormat::Human), file) {
+        (MessageFormat::Human, _) => match level {
+            "error" => log::error!("{}", msg),
+            _ => log::warn!("{}", msg),
+        },
+        (MessageFormat::Github, Some(f)) => eprintln!("::{} file={}::{}", level, escape_property(f), escape_data(msg)),
+        (MessageFormat::Github, None) => eprintln!("::{}::{}", level, escape_data(msg)),
+        (MessageFormat::Annotations, Some(f)) => eprintln!("{}: {}: {}", f, level, msg),
+        (MessageFormat::Annotations, None) => eprintln!("bkrom: {}: {}", level, msg),
+    }
+}
+
+//github workflow command escaping
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
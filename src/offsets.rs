@@ -0,0 +1,175 @@
+use crate::profile::Profile;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OffsetSource {
+    KnownTable,
+    HeaderScan,
+    UserList,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Confidence {
+    High,
+    Medium,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OverlayOffsets {
+    //code start, data start for each overlay followed by the end of the last overlay
+    pub file_offsets: Vec<usize>,
+    pub source: OffsetSource,
+    pub confidence: Confidence,
+}
+
+//tries each automatic offset source in order: the game definition's table, then a rarezip
+//header scan. no offsets are read from the boot code, and a user list (from_list) is not part
+//of the chain, decompress --offsets uses it in place of discover
+pub fn discover(rom : &[u8], profile : &Profile) -> Option<OverlayOffsets> {
+    if let Some(file_offsets) = &profile.overlay_offsets {
+        if validate(rom, file_offsets) {
+            return Some(OverlayOffsets{file_offsets: file_offsets.clone(), source: OffsetSource::KnownTable, confidence: Confidence::High});
+        }
+    }
+
+    scan_offsets(rom, profile)
+}
+
+//offsets derived from the rom alone, for modified or shifted roms no table applies to
+pub fn scan_offsets(rom : &[u8], profile : &Profile) -> Option<OverlayOffsets> {
+    scan(rom, profile.overlays.len()).map(|file_offsets| {
+        OverlayOffsets{file_offsets, source: OffsetSource::HeaderScan, confidence: Confidence::Medium}
+    })
+}
+
+//offsets given by the user for hacks that moved overlays, one overlay per line in
+//compressed rom order as "<name> <start> <end>", # starts a comment
+//the data block start is read from the code block's rarezip header
+pub fn from_list(rom : &[u8], profile : &Profile, list : &str) -> Result<OverlayOffsets, String> {
+    let mut rom_order = profile.overlay_names();
+    profile.swap_rom_order(&mut rom_order);
+    let parse = |n: &str| usize::from_str_radix(n.trim_start_matches("0x").trim_start_matches("0X"), 16).map_err(|_| format!("\"{}\" is not a hex offset", n));
+
+    let entries : Vec<(&str, usize, usize)> = list.lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [name, start, end] => Ok((name, parse(start)?, parse(end)?)),
+            _ => Err(format!("expected \"<name> <start> <end>\", found \"{}\"", line)),
+        })
+        .collect::<Result<_, String>>()?;
+    if entries.len() != rom_order.len() {
+        return Err(format!("expected {} overlays, found {}", rom_order.len(), entries.len()));
+    }
+
+    let mut file_offsets = Vec::with_capacity(2*entries.len() + 1);
+    for (i, ((name, start, end), expected)) in entries.iter().zip(&rom_order).enumerate() {
+        if name != expected {
+            return Err(format!("overlay {} should be {} (compressed rom order), found {}", i, expected, name));
+        }
+        if let Some((_, next_start, _)) = entries.get(i + 1) {
+            if end > next_start {
+                return Err(format!("{} ends (0x{:X}) after {} starts (0x{:X})", name, end, entries[i + 1].0, next_start));
+            }
+        }
+        if *end > rom.len() {
+            return Err(format!("{} ends (0x{:X}) past the end of the 0x{:X} byte rom", name, end, rom.len()));
+        }
+        let code_len = rzip_block_len(rom, *start).ok_or(format!("no rarezip block at {} start 0x{:X}", name, start))?;
+        if start + code_len >= *end {
+            return Err(format!("{} code block runs past its end (0x{:X})", name, end));
+        }
+        let data_len = rzip_block_len(rom, start + code_len).ok_or(format!("no rarezip block at {} data 0x{:X}", name, start + code_len))?;
+        if start + code_len + data_len > *end {
+            return Err(format!("{} data block runs past its end (0x{:X})", name, end));
+        }
+        file_offsets.push(*start);
+        file_offsets.push(start + code_len);
+    }
+    //blocks run up to the next overlay, the last one up to its listed end
+    file_offsets.push(entries.last().map(|(_, _, end)| *end).unwrap_or(0));
+    Ok(OverlayOffsets{file_offsets, source: OffsetSource::UserList, confidence: Confidence::High})
+}
+
+//every listed block should start with a rarezip header
+fn validate(rom : &[u8], file_offsets : &[usize]) -> bool {
+    file_offsets[..file_offsets.len() - 1].iter().all(|offset| {
+        rom.get(*offset .. *offset + 2) == Some(&[0x11, 0x72])
+    })
+}
+
+//length of the rarezip block at offset (header + raw deflate stream)
+pub fn rzip_block_len(rom : &[u8], offset : usize) -> Option<usize> {
+    let block = rom.get(offset..)?;
+    if block.len() < 6 || block[0..2] != [0x11, 0x72] {
+        return None;
+    }
+    let uncompressed_len = u32::from_be_bytes(block[2..6].try_into().unwrap()) as usize;
+    let mut inflater = flate2::Decompress::new(false);
+    let mut output = Vec::with_capacity(uncompressed_len);
+    match inflater.decompress_vec(&block[6..], &mut output, flate2::FlushDecompress::Finish) {
+        Ok(flate2::Status::StreamEnd) if output.len() == uncompressed_len => Some(6 + inflater.total_in() as usize),
+        _ => None,
+    }
+}
+
+//follows the chain of code/data blocks (two per overlay) starting at offset
+fn follow_chain(rom : &[u8], start : usize, overlay_count : usize) -> Option<Vec<usize>> {
+    let mut file_offsets = Vec::with_capacity(2*overlay_count + 1);
+    let mut offset = start;
+    for _ in 0..overlay_count {
+        let data_offset = offset + rzip_block_len(rom, offset)?;
+        let end = data_offset + rzip_block_len(rom, data_offset)?;
+        file_offsets.push(offset);
+        file_offsets.push(data_offset);
+        offset = (end + (16-1)) & !(16-1);
+    }
+    file_offsets.push(offset);
+    Some(file_offsets)
+}
+
+//the overlays are the last rarezip blocks in the rom, search backwards from
+//the end for the first 16 byte aligned header that starts a complete chain
+fn scan(rom : &[u8], overlay_count : usize) -> Option<Vec<usize>> {
+    (0x1000 .. rom.len().saturating_sub(6)).step_by(16).rev()
+        .filter(|offset| {rom[*offset .. *offset + 2] == [0x11, 0x72]})
+        .find_map(|offset| {follow_chain(rom, offset, overlay_count)})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a single overlay whose code and data blocks follow 0x1000 bytes of boot segments
+    fn one_overlay_rom() -> (Profile, Vec<u8>) {
+        let profile = crate::profile::parse("version = \"pal\"\ncic = \"6103\"\noverlays = [\"core1\"]\n").unwrap();
+        let mut rom = vec![0; 0x1000];
+        rom.extend(rarezip::bk::zip(&[1; 0x40]));
+        rom.extend(rarezip::bk::zip(&[2; 0x40]));
+        rom.resize(rom.len().next_multiple_of(16), 0);
+        (profile, rom)
+    }
+
+    #[test]
+    fn user_list() {
+        let (profile, rom) = one_overlay_rom();
+        let offsets = from_list(&rom, &profile, &format!("core1 0x1000 0x{:X} # the only one\n", rom.len())).unwrap();
+        assert_eq!(offsets.file_offsets, [0x1000, 0x1000 + rzip_block_len(&rom, 0x1000).unwrap(), rom.len()]);
+    }
+
+    #[test]
+    fn user_list_past_rom_end() {
+        let (profile, rom) = one_overlay_rom();
+        assert!(from_list(&rom, &profile, &format!("core1 0x1000 0x{:X}\n", rom.len() + 0x10)).is_err());
+    }
+
+    #[test]
+    fn user_list_bad_data_block() {
+        let (profile, mut rom) = one_overlay_rom();
+        let data_start = 0x1000 + rzip_block_len(&rom, 0x1000).unwrap();
+        rom[data_start] = 0;
+        assert!(from_list(&rom, &profile, &format!("core1 0x1000 0x{:X}\n", rom.len())).is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//IPS and BPS patch creation and application
+
+use bk_rom_comp::BkRomError;
+
+//IPS offsets are 24 bit
+pub const IPS_MAX_SIZE : usize = 0x1000000;
+//"EOF" read as a record offset
+const IPS_EOF : usize = 0x454F46;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+}
+
+impl PatchFormat {
+    //picks the format from the patch file extension, defaulting to bps
+    pub fn from_path(path: &str) -> Self {
+        match std::path::Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("ips") => PatchFormat::Ips,
+            _ => PatchFormat::Bps,
+        }
+    }
+}
+
+pub fn create(format: PatchFormat, source: &[u8], target: &[u8]) -> Result<Vec<u8>, BkRomError> {
+    match format {
+        PatchFormat::Ips => ips(source, target),
+        PatchFormat::Bps => Ok(bps(source, target)),
+    }
+}
+
+//ranges of target that differ from source (bytes past the end of source always differ)
+fn changed_runs(source: &[u8], target: &[u8]) -> Vec<std::ops::Range<usize>> {
+    let mut runs : Vec<std::ops::Range<usize>> = Vec::new();
+    for (i, byte) in target.iter().enumerate() {
+        if source.get(i) == Some(byte) {
+            continue;
+        }
+        match runs.last_mut() {
+            Some(run) if run.end == i => run.end += 1,
+            _ => runs.push(i..i + 1),
+        }
+    }
+    runs
+}
+
+pub fn ips(source: &[u8], target: &[u8]) -> Result<Vec<u8>, BkRomError> {
+    if target.len() > IPS_MAX_SIZE {
+        return Err(BkRomError::BadPatch(format!("IPS patches can only address 16MiB, the 0x{:X} byte rom needs a .bps patch", target.len())));
+    }
+    let mut patch = b"PATCH".to_vec();
+    for run in changed_runs(source, target) {
+        let mut offset = run.start;
+        while offset < run.end {
+            //a record starting at 0x454F46 would read as "EOF", start one byte early
+            let start = if offset == IPS_EOF {offset - 1} else {offset};
+            let len = usize::min(0xFFFF, run.end - start);
+            patch.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+            patch.extend_from_slice(&(len as u16).to_be_bytes());
+            patch.extend_from_slice(&target[start .. start + len]);
+            offset = start + len;
+        }
+    }
+    patch.extend_from_slice(b"EOF");
+    //truncation extension
+    if target.len() < source.len() {
+        patch.extend_from_slice(&(target.len() as u32).to_be_bytes()[1..]);
+    }
+    Ok(patch)
+}
+
+fn bps_number(patch: &mut Vec<u8>, mut data: u64) {
+    loop {
+        let x = (data & 0x7f) as u8;
+        data >>= 7;
+        if data == 0 {
+            patch.push(0x80 | x);
+            break;
+        }
+        patch.push(x);
+        data -= 1;
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+pub fn bps(source: &[u8], target: &[u8]) -> Vec<u8> {
+    const SOURCE_READ : u64 = 0;
+    const TARGET_READ : u64 = 1;
+
+    let mut patch = b"BPS1".to_vec();
+    bps_number(&mut patch, source.len() as u64);
+    bps_number(&mut patch, target.len() as u64);
+    bps_number(&mut patch, 0); //no metadata
+
+    let mut output_offset = 0;
+    for run in changed_runs(source, target) {
+        if run.start > output_offset {
+            bps_number(&mut patch, ((run.start - output_offset - 1) as u64) << 2 | SOURCE_READ);
+        }
+        bps_number(&mut patch, ((run.len() - 1) as u64) << 2 | TARGET_READ);
+        patch.extend_from_slice(&target[run.clone()]);
+        output_offset = run.end;
+    }
+    if target.len() > output_offset {
+        bps_number(&mut patch, ((target.len() - output_offset - 1) as u64) << 2 | SOURCE_READ);
+    }
+
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    let patch_crc = crc32(&patch);
+    patch.extend_from_slice(&patch_crc.to_le_bytes());
+    patch
+}
+
+//applies an IPS or BPS patch, picked from its magic, to source
+pub fn apply(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    match patch {
+        [b'P', b'A', b'T', b'C', b'H', ..] => apply_ips(source, patch),
+        [b'B', b'P', b'S', b'1', ..] => apply_bps(source, patch),
+        [0xD6, 0xC3, 0xC4, ..] => Err(String::from("xdelta patches are not supported, apply it with xdelta3 first")),
+        _ => Err(String::from("not an IPS or BPS patch")),
+    }
+}
+
+fn apply_ips(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let truncated = || String::from("truncated IPS patch");
+    let mut target = source.to_vec();
+    let mut pos = 5;
+    loop {
+        let record = patch.get(pos .. pos + 3).ok_or_else(truncated)?;
+        pos += 3;
+        if record == b"EOF" {
+            break;
+        }
+        let offset = u32::from_be_bytes([0, record[0], record[1], record[2]]) as usize;
+        let len = patch.get(pos .. pos + 2).map(|l| u16::from_be_bytes([l[0], l[1]]) as usize).ok_or_else(truncated)?;
+        pos += 2;
+        //a zero length record is a run of one byte
+        let bytes = match len {
+            0 => {
+                let run = patch.get(pos .. pos + 3).ok_or_else(truncated)?;
+                pos += 3;
+                vec![run[2]; u16::from_be_bytes([run[0], run[1]]) as usize]
+            },
+            _ => {
+                let bytes = patch.get(pos .. pos + len).ok_or_else(truncated)?.to_vec();
+                pos += len;
+                bytes
+            },
+        };
+        if target.len() < offset + bytes.len() {
+            target.resize(offset + bytes.len(), 0);
+        }
+        target[offset .. offset + bytes.len()].copy_from_slice(&bytes);
+    }
+    //truncation extension
+    if let Some(len) = patch.get(pos .. pos + 3) {
+        target.truncate(u32::from_be_bytes([0, len[0], len[1], len[2]]) as usize);
+    }
+    Ok(target)
+}
+
+fn read_bps_number(patch: &[u8], pos: &mut usize) -> Result<u64, String> {
+    let mut data = 0;
+    let mut shift = 1;
+    loop {
+        let x = *patch.get(*pos).ok_or("truncated BPS patch")? as u64;
+        *pos += 1;
+        data += (x & 0x7f) * shift;
+        if x & 0x80 != 0 {
+            return Ok(data);
+        }
+        shift <<= 7;
+        data += shift;
+    }
+}
+
+fn apply_bps(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if patch.len() < 16 {
+        return Err(String::from("truncated BPS patch"));
+    }
+    let (body, crcs) = patch.split_at(patch.len() - 12);
+    let crc = |i: usize| u32::from_le_bytes(crcs[i*4 .. i*4 + 4].try_into().unwrap());
+    if crc32(&patch[.. patch.len() - 4]) != crc(2) {
+        return Err(String::from("BPS patch is corrupt (patch crc mismatch)"));
+    }
+    if crc32(source) != crc(0) {
+        return Err(String::from("rom is not the one the BPS patch was made for (source crc mismatch)"));
+    }
+
+    let mut pos = 4;
+    let source_size = read_bps_number(body, &mut pos)? as usize;
+    let target_size = read_bps_number(body, &mut pos)? as usize;
+    let metadata_size = read_bps_number(body, &mut pos)? as usize;
+    pos += metadata_size;
+    if source_size != source.len() {
+        return Err(format!("BPS patch expects a 0x{:X} byte rom, got 0x{:X} bytes", source_size, source.len()));
+    }
+
+    let mut target : Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset : i64 = 0;
+    let mut target_offset : i64 = 0;
+    let out_of_range = || String::from("BPS patch reads out of range");
+    while pos < body.len() {
+        let command = read_bps_number(body, &mut pos)?;
+        let len = (command >> 2) as usize + 1;
+        let relative = |pos: &mut usize| read_bps_number(body, pos).map(|n| if n & 1 != 0 {-((n >> 1) as i64)} else {(n >> 1) as i64});
+        match command & 3 {
+            //source read
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(source.get(start .. start + len).ok_or_else(out_of_range)?);
+            },
+            //target read
+            1 => {
+                target.extend_from_slice(body.get(pos .. pos + len).ok_or_else(out_of_range)?);
+                pos += len;
+            },
+            //source copy
+            2 => {
+                source_offset += relative(&mut pos)?;
+                let start = usize::try_from(source_offset).map_err(|_| out_of_range())?;
+                target.extend_from_slice(source.get(start .. start + len).ok_or_else(out_of_range)?);
+                source_offset += len as i64;
+            },
+            //target copy, byte by byte as the copy may overlap what it writes
+            _ => {
+                target_offset += relative(&mut pos)?;
+                for _ in 0..len {
+                    let byte = *target.get(usize::try_from(target_offset).map_err(|_| out_of_range())?).ok_or_else(out_of_range)?;
+                    target.push(byte);
+                    target_offset += 1;
+                }
+            },
+        }
+    }
+
+    if target.len() != target_size || crc32(&target) != crc(1) {
+        return Err(String::from("patched rom does not match the BPS patch's target crc"));
+    }
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roms() -> (Vec<u8>, Vec<u8>) {
+        let source : Vec<u8> = (0..0x30000u32).map(|i| (i*7 % 251) as u8).collect();
+        let mut target = source.clone();
+        target[0x10..0x20].fill(0xAA);
+        //a run longer than one IPS record
+        target[0x1000..0x1000 + 0x12345].iter_mut().for_each(|b| *b ^= 0xFF);
+        target.extend_from_slice(b"grown");
+        (source, target)
+    }
+
+    #[test]
+    fn bps_round_trip() {
+        let (source, target) = roms();
+        assert_eq!(apply(&source, &bps(&source, &target)).unwrap(), target);
+        assert_eq!(apply(&target, &bps(&target, &source)).unwrap(), source);
+    }
+
+    #[test]
+    fn ips_round_trip() {
+        let (source, target) = roms();
+        assert_eq!(apply(&source, &ips(&source, &target).unwrap()).unwrap(), target);
+        //shrinking uses the truncation extension
+        assert_eq!(apply(&target, &ips(&target, &source).unwrap()).unwrap(), source);
+    }
+
+    //offsets of every record in a patch without rle records, reading up to the "EOF" marker
+    //and an optional truncation size so a record at 0x454F46 is still read as a record
+    fn ips_record_offsets(patch: &[u8]) -> Vec<usize> {
+        let mut offsets = Vec::new();
+        let mut pos = 5;
+        while patch.len() - pos > 6 {
+            offsets.push(u32::from_be_bytes([0, patch[pos], patch[pos + 1], patch[pos + 2]]) as usize);
+            pos += 5 + u16::from_be_bytes([patch[pos + 3], patch[pos + 4]]) as usize;
+        }
+        assert_eq!(&patch[pos .. pos + 3], b"EOF");
+        offsets
+    }
+
+    #[test]
+    fn ips_never_writes_eof_offset() {
+        let source = vec![0; IPS_EOF + 0x20000];
+        let runs = [
+            //a run whose second record would start at 0x454F46
+            IPS_EOF - 0xFFFF .. IPS_EOF + 0x10,
+            //a run starting exactly there
+            IPS_EOF .. IPS_EOF + 4,
+            //and one well after it
+            IPS_EOF + 0x100 .. IPS_EOF + 0x110,
+        ];
+        for run in runs {
+            let mut target = source.clone();
+            target[run].fill(1);
+            let patch = ips(&source, &target).unwrap();
+            assert!(!ips_record_offsets(&patch).contains(&IPS_EOF));
+            assert_eq!(apply(&source, &patch).unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn ips_rejects_large_roms() {
+        let target = vec![0; IPS_MAX_SIZE + 1];
+        assert!(matches!(ips(&[], &target), Err(BkRomError::BadPatch(_))));
+    }
+}
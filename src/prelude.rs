@@ -0,0 +1,13 @@
+//everything a downstream tool needs to load, compress and decompress a rom, glob import
+//it with `use bk_rom_comp::prelude::*;`
+//semver: names exported here only change in a major release, new ones may be added in a
+//minor release, so avoid defining items that clash with them. pub items outside the
+//prelude (cic, message, offsets helpers) carry the same guarantee but move more often
+pub use crate::{BkRomError, Profile, Rom};
+pub use crate::{GameId, GameVersion, Overlay, Symbol, ByteOrder, CompressedOverlays, OverlayCrcs, TamperCheck};
+pub use crate::anti_tamper::CrcSlot;
+pub use crate::store::Store;
+pub use crate::rom::SymbolFormat;
+pub use crate::{read_elf_symbols, parse_elf_symbols, read_symbol_addrs, slice_overlays, compress_overlays, compress_rom};
+pub use crate::{Callbacks, compress_overlays_with, compress_rom_with};
+pub use crate::{decompress_rom, verify_round_trip, check_anti_tamper, as_big_endian, rom_to_big_endian};
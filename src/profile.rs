@@ -0,0 +1,150 @@
+//what this build of the tool knows about each supported rom, embedded from
+//profile/*.toml and replaceable with --game-def for hacks and new versions
+
+use std::sync::LazyLock;
+use crate::anti_tamper::{self, CrcSlot};
+use crate::{BkRomError, GameId, GameVersion};
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub game_id: GameId,
+    //name accepted by -v/--game-version
+    pub cli_name: String,
+    //md5 of the original (compressed) big endian rom, unknown for hacks
+    pub compressed_md5: Option<String>,
+    pub cic: String,
+    //overlays in uncompressed rom order
+    pub overlays: Vec<String>,
+    //indices of overlays that trade places in the compressed rom
+    pub rom_order_swaps: Vec<(usize, usize)>,
+    //code start, data start for each overlay in compressed rom order followed by the end of the last overlay
+    pub overlay_offsets: Option<Vec<usize>>,
+    //rom offset of the asset table, assets extract falls back to the overlay layout without it
+    pub asset_table: Option<usize>,
+    pub anti_tamper: Option<Vec<CrcSlot>>,
+}
+
+const BUILT_IN : [&str; 4] = [
+    include_str!("profile/us.v10.toml"),
+    include_str!("profile/pal.toml"),
+    include_str!("profile/jp.toml"),
+    include_str!("profile/us.v11.toml"),
+];
+
+pub static PROFILES : LazyLock<Vec<Profile>> = LazyLock::new(|| {
+    BUILT_IN.iter().map(|def| parse(def).expect("built in game definition is invalid")).collect()
+});
+
+//short md5 over the built in definitions, changes whenever an offset or slot table is edited
+pub fn revision() -> String {
+    let mut context = md5::Context::new();
+    BUILT_IN.iter().for_each(|def| context.consume(def.as_bytes()));
+    format!("{:x}", context.compute())[..8].to_string()
+}
+
+impl Profile {
+    pub fn overlay_names(&self) -> Vec<&str> {
+        self.overlays.iter().map(String::as_str).collect()
+    }
+
+    //reorders per overlay items between uncompressed and compressed rom order, the swaps
+    //are their own inverse so this works in both directions
+    pub fn swap_rom_order<T>(&self, items: &mut [T]) {
+        self.rom_order_swaps.iter().for_each(|(a, b)| items.swap(*a, *b));
+    }
+
+    //no version is built without its crcs patched, a missing table is an error rather than no slots
+    pub fn anti_tamper_slots(&self) -> Result<&[CrcSlot], BkRomError> {
+        self.anti_tamper.as_deref().ok_or_else(|| BkRomError::UnsupportedVersion(
+            format!("no anti-tamper slots are known for {}, pass them with --antitamper-config", self.game_id.version_string())))
+    }
+
+    //retail rom range of each overlay in compressed rom order, from its code start to the next one's
+    pub fn overlay_slots(&self) -> Option<Vec<std::ops::Range<usize>>> {
+        let offsets = self.overlay_offsets.as_ref()?;
+        Some(offsets.iter().step_by(2).collect::<Vec<_>>().windows(2).map(|w| *w[0] .. *w[1]).collect())
+    }
+
+    //replaces the overlay list, swaps follow their overlays by name and are dropped
+    //when one of them is gone, the known offsets only hold for the original list
+    pub fn set_overlays(&mut self, overlays: Vec<String>) {
+        if overlays == self.overlays {
+            return;
+        }
+        let index = |name: &String| overlays.iter().position(|n| n == name);
+        self.rom_order_swaps = self.rom_order_swaps.iter()
+            .filter_map(|(a, b)| Some((index(&self.overlays[*a])?, index(&self.overlays[*b])?)))
+            .collect();
+        self.overlays = overlays;
+        self.overlay_offsets = None;
+    }
+}
+
+pub fn builtin(game_id: &GameId) -> &'static Profile {
+    PROFILES.iter().find(|p| p.game_id.version_string() == game_id.version_string()).expect("every version has a built in game definition")
+}
+
+pub fn find(cli_name: &str) -> Option<&'static Profile> {
+    PROFILES.iter().find(|p| p.cli_name == cli_name)
+}
+
+//reads a definition supplied by the user, e.g. a copy of a built in one with moved overlays
+pub fn load(path: &str) -> Result<Profile, BkRomError> {
+    let def = std::fs::read_to_string(path).map_err(BkRomError::file(path))?;
+    parse(&def).map_err(|e| BkRomError::BadSymbol(format!("\"{}\": {}", path, e)))
+}
+
+pub fn parse(def: &str) -> Result<Profile, String> {
+    let table : toml::Table = def.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+    let string = |name: &str| table.get(name).and_then(|v| v.as_str()).map(str::to_string).ok_or(format!("expected a {} string", name));
+    let strings = |value: &toml::Value| value.as_array().and_then(|a| a.iter().map(|v| v.as_str().map(str::to_string)).collect::<Option<Vec<_>>>());
+
+    let cli_name = string("version")?;
+    let game_id = match cli_name.as_str() {
+        "us.v10" => GameId::BanjoKazooie(GameVersion::USA),
+        "pal" => GameId::BanjoKazooie(GameVersion::PAL),
+        "jp" => GameId::BanjoKazooie(GameVersion::JP),
+        "us.v11" => GameId::BanjoKazooie(GameVersion::USARevA),
+        v => return Err(format!("unknown version \"{}\", expected us.v10, pal, jp or us.v11", v)),
+    };
+    let overlays = table.get("overlays").and_then(strings).ok_or("expected an overlays list")?;
+    let overlay_index = |name: &str| overlays.iter().position(|n| n == name).ok_or(format!("rom_order_swaps refers to unknown overlay {}", name));
+    let rom_order_swaps = match table.get("rom_order_swaps") {
+        Some(swaps) => swaps.as_array().ok_or("expected a rom_order_swaps list")?.iter().map(|pair| {
+            match strings(pair).as_deref() {
+                Some([a, b]) => Ok((overlay_index(a)?, overlay_index(b)?)),
+                _ => Err(String::from("rom_order_swaps entries should be pairs of overlay names")),
+            }
+        }).collect::<Result<_, String>>()?,
+        None => Vec::new(),
+    };
+    let overlay_offsets = match table.get("overlay_offsets") {
+        Some(offsets) => {
+            let offsets = offsets.as_array().and_then(|a| a.iter().map(|v| v.as_integer().map(|i| i as usize)).collect::<Option<Vec<_>>>())
+                .ok_or("expected an overlay_offsets list of integers")?;
+            if offsets.len() != 2*overlays.len() + 1 {
+                return Err(format!("expected {} overlay_offsets (code and data start of each overlay and the end), found {}", 2*overlays.len() + 1, offsets.len()));
+            }
+            Some(offsets)
+        },
+        None => None,
+    };
+
+    Ok(Profile {
+        game_id,
+        cli_name,
+        compressed_md5: string("compressed_md5").ok(),
+        cic: string("cic")?,
+        overlays,
+        rom_order_swaps,
+        overlay_offsets,
+        asset_table: match table.get("asset_table") {
+            Some(offset) => Some(offset.as_integer().ok_or("expected an integer asset_table")? as usize),
+            None => None,
+        },
+        anti_tamper: match table.contains_key("slot") {
+            true => Some(anti_tamper::from_table(&table)?),
+            false => None,
+        },
+    })
+}
@@ -0,0 +1,28 @@
+//progress bars for the per-overlay loops, drawn on stderr so they don't mix with
+//output meant for other tools and hidden when stderr is not a terminal
+use std::sync::OnceLock;
+use indicatif::{ProgressBar, ProgressStyle};
+use bk_rom_comp::message;
+
+pub fn bar(task: &str, total: usize) -> ProgressBar {
+    if !log::log_enabled!(log::Level::Info) {
+        return ProgressBar::hidden();
+    }
+    let bar = ProgressBar::new(total as u64);
+    bar.set_style(ProgressStyle::with_template("{prefix} [{bar:32}] {pos}/{len} {msg}").unwrap().progress_chars("=> "));
+    bar.set_prefix(task.to_string());
+    bar
+}
+
+//shows the library's compression progress
+pub fn install(task: &'static str) {
+    static BAR : OnceLock<ProgressBar> = OnceLock::new();
+    message::set_progress_hook(Box::new(move |item, done, total| {
+        let bar = BAR.get_or_init(|| bar(task, total));
+        bar.set_message(item.to_string());
+        bar.set_position(done as u64);
+        if done == total {
+            bar.finish_and_clear();
+        }
+    }));
+}
@@ -0,0 +1,40 @@
+use std::io::{BufRead, IsTerminal, Write};
+use bk_rom_comp::BkRomError;
+
+//prompting is only done when a person is at the keyboard
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+//asks until validate accepts the answer
+pub fn ask<T, F: Fn(&str) -> Result<T, String>>(question: &str, validate: F) -> Result<T, BkRomError> {
+    let stdin = std::io::stdin();
+    loop {
+        print!("{}: ", question);
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer)? == 0 {
+            return Err(BkRomError::NoInput(question.to_string()));
+        }
+        //drag and drop onto a terminal often quotes the path
+        let answer = answer.trim().trim_matches(|c| c == '"' || c == '\'');
+        match validate(answer) {
+            Ok(v) => return Ok(v),
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+pub fn existing_file(answer: &str) -> Result<String, String> {
+    match std::fs::metadata(answer) {
+        Ok(m) if m.is_file() => Ok(answer.to_string()),
+        _ => Err(format!("\"{}\" is not a file", answer)),
+    }
+}
+
+pub fn any_path(answer: &str) -> Result<String, String> {
+    match answer.is_empty() {
+        true => Err(String::from("a path is required")),
+        false => Ok(answer.to_string()),
+    }
+}
@@ -0,0 +1,385 @@
+use std::io::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{bk_crc, cic, find_elf_symbol, BkRomError, Overlay, Symbol};
+
+//size of the retail roms, compressed roms are padded to it by default
+pub const ROM_SIZE : usize = 0x1000000;
+//the cic checksum covers 1MB after the bootcode, so no rom can be shorter
+const MIN_ROM_SIZE : usize = 0x101000;
+
+//something the compressed rom can be laid out into
+pub trait RomImage: Sync {
+    fn write_at(&self, bytes: &[u8], offset: usize) -> std::io::Result<()>;
+    fn read_at(&self, bytes: &mut [u8], offset: usize) -> std::io::Result<()>;
+}
+
+#[cfg(unix)]
+impl RomImage for std::fs::File {
+    fn write_at(&self, bytes: &[u8], offset: usize) -> std::io::Result<()> {
+        use std::os::unix::fs::FileExt;
+        self.write_all_at(bytes, offset as u64)
+    }
+
+    fn read_at(&self, bytes: &mut [u8], offset: usize) -> std::io::Result<()> {
+        use std::os::unix::fs::FileExt;
+        self.read_exact_at(bytes, offset as u64)
+    }
+}
+
+#[cfg(windows)]
+impl RomImage for std::fs::File {
+    fn write_at(&self, mut bytes: &[u8], mut offset: usize) -> std::io::Result<()> {
+        use std::os::windows::fs::FileExt;
+        while !bytes.is_empty() {
+            let n = self.seek_write(bytes, offset as u64)?;
+            bytes = &bytes[n..];
+            offset += n;
+        }
+        Ok(())
+    }
+
+    fn read_at(&self, mut bytes: &mut [u8], mut offset: usize) -> std::io::Result<()> {
+        use std::os::windows::fs::FileExt;
+        while !bytes.is_empty() {
+            let n = self.seek_read(bytes, offset as u64)?;
+            if n == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            bytes = &mut bytes[n..];
+            offset += n;
+        }
+        Ok(())
+    }
+}
+
+//in memory image, used when nothing should touch the disk
+impl RomImage for Mutex<Vec<u8>> {
+    fn write_at(&self, bytes: &[u8], offset: usize) -> std::io::Result<()> {
+        self.lock().unwrap()[offset .. offset + bytes.len()].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn read_at(&self, bytes: &mut [u8], offset: usize) -> std::io::Result<()> {
+        bytes.copy_from_slice(&self.lock().unwrap()[offset .. offset + bytes.len()]);
+        Ok(())
+    }
+}
+
+//an existing rom file that is only written where its bytes differ, counting what was rewritten
+pub struct Incremental<'a> {
+    file: &'a std::fs::File,
+    rewritten: AtomicUsize,
+}
+
+impl RomImage for Incremental<'_> {
+    fn write_at(&self, bytes: &[u8], offset: usize) -> std::io::Result<()> {
+        let mut existing = vec![0; bytes.len()];
+        self.file.read_at(&mut existing, offset)?;
+        let first = bytes.iter().zip(&existing).position(|(a, b)| a != b);
+        let last = bytes.iter().zip(&existing).rposition(|(a, b)| a != b);
+        if let (Some(first), Some(last)) = (first, last) {
+            self.file.write_at(&bytes[first ..= last], offset + first)?;
+            self.rewritten.fetch_add(last + 1 - first, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    fn read_at(&self, bytes: &mut [u8], offset: usize) -> std::io::Result<()> {
+        self.file.read_at(bytes, offset)
+    }
+}
+
+//how the rzip offset symbols are written out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolFormat {
+    //gnu ld assignments
+    Ld,
+    Json,
+    //nm style absolute symbols
+    Nm,
+    //.definelabel directives
+    Armips,
+}
+
+impl SymbolFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SymbolFormat::Ld => "ld",
+            SymbolFormat::Json => "json",
+            SymbolFormat::Nm => "txt",
+            SymbolFormat::Armips => "asm",
+        }
+    }
+}
+
+//everything needed to lay out the compressed rom
+pub struct Rom<'a> {
+    pub uncompressed_rom: &'a [u8],
+    pub symbols: &'a [Symbol],
+    pub bk_boot_info: Overlay,
+    pub bk_boot_bytes: Vec<u8>,
+    pub core1_code_crc: (u32, u32),
+    pub core1_data_crc: (u32, u32),
+    //in rom order
+    pub overlay_names: Vec<&'a str>,
+    pub overlay_offsets: Vec<Overlay>,
+    //code and data crc of each overlay
+    pub overlay_crcs: Vec<crate::OverlayCrcs>,
+    pub rzip_bytes: Vec<Vec<u8>>,
+    //(overlay, "code" or "data") stored uncompressed because compressing grew it
+    pub stored: Vec<(String, &'static str)>,
+    pub overlay_start_offset: usize,
+    //size to pad the rom to, None to end it right after the overlays
+    pub pad_to: Option<usize>,
+    pub fill: u8,
+    //fixed rom range of each overlay (rom order), None to pack them back to back
+    pub slots: Option<Vec<std::ops::Range<usize>>>,
+    //(offset, bytes) written over the header before the crc is calculated
+    pub header_patches: Vec<(usize, Vec<u8>)>,
+}
+
+impl Rom<'_> {
+    //rom offset of each compressed overlay
+    pub fn overlay_rom_offsets(&self) -> Vec<usize> {
+        if let Some(slots) = &self.slots {
+            return slots.iter().map(|slot| slot.start).collect();
+        }
+        self.rzip_bytes.iter().scan(self.overlay_start_offset, |offset, rzip| {
+            let start = *offset;
+            *offset += rzip.len();
+            Some(start)
+        }).collect()
+    }
+
+    //calculate end of rzip
+    pub fn overlay_end_offset(&self) -> usize {
+        if let Some(slots) = &self.slots {
+            return slots.iter().zip(&self.rzip_bytes).map(|(slot, rzip)| usize::max(slot.end, slot.start + rzip.len())).max().unwrap_or(self.overlay_start_offset);
+        }
+        self.rzip_bytes.iter().fold(self.overlay_start_offset, |acc, rzip|{acc + rzip.len()})
+    }
+
+    //(name, slot size, compressed size) of every overlay that doesn't fit its fixed slot
+    pub fn slot_overflows(&self) -> Vec<(&str, usize, usize)> {
+        let Some(slots) = &self.slots else {
+            return Vec::new();
+        };
+        self.overlay_names.iter().zip(slots).zip(&self.rzip_bytes)
+            .filter(|((_, slot), rzip)| rzip.len() > slot.len())
+            .map(|((name, slot), rzip)| (*name, slot.len(), rzip.len()))
+            .collect()
+    }
+
+    pub fn rom_size(&self) -> usize {
+        self.pad_to.unwrap_or(usize::max(self.overlay_end_offset(), MIN_ROM_SIZE))
+    }
+
+    pub fn crc_rom_start(&self) -> Result<usize, BkRomError> {
+        Ok(find_elf_symbol(self.symbols, "crc_ROM_START")?.value as usize)
+    }
+
+    //(name, value) of every rzip ROM_START/ROM_END symbol in rom order
+    pub fn rzip_symbols(&self) -> Vec<(String, usize)> {
+        self.overlay_names.iter().zip(self.rzip_bytes.iter()).zip(self.overlay_rom_offsets()).flat_map(|((name, rzip), i_offset)| {
+            [(format!("boot_{}_rzip_ROM_START", name), i_offset), (format!("boot_{}_rzip_ROM_END", name), i_offset + rzip.len())]
+        }).collect()
+    }
+
+    pub fn write_symbols(&self, path: &std::path::Path, format: SymbolFormat) -> Result<(), BkRomError>{
+        let mut symbol_file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let symbols = self.rzip_symbols();
+        match format {
+            SymbolFormat::Ld => for (name, value) in &symbols {
+                writeln!(symbol_file, "{} = 0x{:X?};", name, value)?;
+            },
+            SymbolFormat::Json => {
+                let entries : Vec<String> = symbols.iter().map(|(name, value)| format!("  \"{}\": {}", name, value)).collect();
+                writeln!(symbol_file, "{{\n{}\n}}", entries.join(",\n"))?;
+            },
+            SymbolFormat::Nm => for (name, value) in &symbols {
+                writeln!(symbol_file, "{:08X} A {}", value, name)?;
+            },
+            SymbolFormat::Armips => for (name, value) in &symbols {
+                writeln!(symbol_file, ".definelabel {}, 0x{:X}", name, value)?;
+            },
+        }
+        symbol_file.flush()?;
+        Ok(())
+    }
+
+    pub fn write_file(&self, path: &std::path::Path) -> Result<(), BkRomError>{
+        //println!("Creating ROM {} => {}", config.uncomp_rom_path, config.out_path);
+        let out_file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        out_file.set_len(self.rom_size() as u64)?;
+        self.write(&out_file)
+    }
+
+    //rewrites only the bytes of an existing rom of the same size that changed, e.g. the
+    //overlays touched since the last build and the crcs, returns how many bytes were written
+    pub fn update_file(&self, path: &std::path::Path) -> Result<usize, BkRomError>{
+        let out_file = match std::fs::OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) if file.metadata()?.len() == self.rom_size() as u64 => file,
+            _ => {
+                self.write_file(path)?;
+                return Ok(self.rom_size());
+            },
+        };
+        let image = Incremental{file: &out_file, rewritten: AtomicUsize::new(0)};
+        self.write(&image)?;
+        Ok(image.rewritten.into_inner())
+    }
+
+    pub fn to_vec(&self) -> Result<Vec<u8>, BkRomError> {
+        let image = Mutex::new(vec![0; self.rom_size()]);
+        self.write(&image)?;
+        Ok(image.into_inner().unwrap())
+    }
+
+    pub fn write(&self, out: &impl RomImage) -> Result<(), BkRomError>{
+        let uncompressed_rom = self.uncompressed_rom;
+        let crc_rom_start = self.crc_rom_start()?;
+        let overlay_start_offset = self.overlay_start_offset;
+        if crc_rom_start + 0x20 > overlay_start_offset {
+            return Err(BkRomError::BadSymbol(format!("crc_ROM_START (0x{:X}) is not before the first overlay", crc_rom_start)));
+        }
+        let overflows = self.slot_overflows();
+        if !overflows.is_empty() {
+            let report : Vec<String> = overflows.iter().map(|(name, available, required)| {
+                format!("  {:<10} available 0x{:X}, required 0x{:X} (0x{:X} over)", name, available, required, required - available)
+            }).collect();
+            return Err(BkRomError::BadRom(format!("overlays don't fit their retail slots:\n{}", report.join("\n"))));
+        }
+        let rom_size = self.rom_size();
+        if self.overlay_end_offset() > rom_size {
+            return Err(BkRomError::BadRom(format!("compressed overlays end at 0x{:X}, past the end of the 0x{:X} byte rom", self.overlay_end_offset(), rom_size)));
+        }
+        if rom_size < MIN_ROM_SIZE {
+            return Err(BkRomError::BadRom(format!("a 0x{:X} byte rom is too small to hold the cic checksummed area", rom_size)));
+        }
+
+        //  update crc_bin
+        //println!("Calculating ROM CRCs...");
+        let bk_boot_crc = bk_crc(&self.bk_boot_bytes);
+        let mut rom_crc_bytes: Vec<u8> = vec![0; 0x20];
+        rom_crc_bytes.splice(0..4, bk_boot_crc.0.to_be_bytes());
+        rom_crc_bytes.splice(4..8, bk_boot_crc.1.to_be_bytes());
+        rom_crc_bytes.splice(8..0xC, self.core1_code_crc.0.to_be_bytes());
+        rom_crc_bytes.splice(0xC..0x10, self.core1_code_crc.1.to_be_bytes());
+        rom_crc_bytes.splice(0x10..0x14, self.core1_data_crc.0.to_be_bytes());
+        rom_crc_bytes.splice(0x14..0x18, self.core1_data_crc.1.to_be_bytes());
+
+        //  create output
+        out.write_at(&uncompressed_rom[..self.bk_boot_info.uncompressed_rom.start], 0)?;
+        for (offset, bytes) in &self.header_patches {
+            out.write_at(bytes, *offset)?;
+        }
+        out.write_at(&self.bk_boot_bytes, self.bk_boot_info.uncompressed_rom.start)?;
+        out.write_at(&rom_crc_bytes, crc_rom_start)?;
+        out.write_at(&uncompressed_rom[crc_rom_start + 0x20 .. overlay_start_offset], crc_rom_start + 0x20)?;
+        std::thread::scope(|scope| {
+            let writes : Vec<_> = self.rzip_bytes.iter().zip(self.overlay_rom_offsets()).map(|(rzip_bin, offset)| {
+                scope.spawn(move || out.write_at(rzip_bin, offset))
+            }).collect();
+            writes.into_iter().try_for_each(|write| write.join().unwrap())
+        })?;
+
+        //gaps left between fixed slots, including the unused tail of each slot, and the space
+        //after the overlays
+        let mut placed : Vec<(usize, usize)> = self.overlay_rom_offsets().into_iter().zip(self.rzip_bytes.iter().map(Vec::len)).collect();
+        placed.sort();
+        let mut gaps : Vec<std::ops::Range<usize>> = placed.windows(2).map(|w| w[0].0 + w[0].1 .. w[1].0).filter(|gap| !gap.is_empty()).collect();
+        if let Some(&(first, _)) = placed.first() {
+            gaps.push(overlay_start_offset .. first);
+        }
+        //the last blob can end before its slot does
+        let placed_end = placed.last().map(|(start, len)| start + len).unwrap_or(overlay_start_offset);
+        gaps.push(placed_end .. rom_size);
+        let padding = [self.fill; 0x10000];
+        for gap in gaps {
+            let mut offset = gap.start;
+            while offset < gap.end {
+                let len = usize::min(padding.len(), gap.end - offset);
+                out.write_at(&padding[..len], offset)?;
+                offset += len;
+            }
+        }
+
+        //update n64 cic crc
+        let mut crc_bytes = vec![0; 0x101000];
+        out.read_at(&mut crc_bytes, 0)?;
+        let crc_value = cic::calculate_crc(&crc_bytes).ok_or(BkRomError::BadRom(String::from("unknown cic bootcode, can't calculate the header crc")))?;
+        let crc_bytes : Vec<u8> = crc_value.into_iter().flat_map(u32::to_be_bytes).collect();
+        out.write_at(&crc_bytes, 0x10)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a rom with two overlays in fixed slots, both blobs shorter than their slot
+    fn slotted_rom<'a>(uncompressed_rom: &'a [u8], symbols: &'a [Symbol], rzip_bytes: Vec<Vec<u8>>) -> Rom<'a> {
+        let overlay = |name: &str| Overlay{name: name.to_string(), text: 0..0, data: 0..0, bss: 0..0, uncompressed_rom: 0..0};
+        Rom{
+            uncompressed_rom,
+            symbols,
+            bk_boot_info: Overlay{uncompressed_rom: 0x200..0x300, ..overlay("bk_boot")},
+            bk_boot_bytes: vec![0xB0; 0x100],
+            core1_code_crc: (0, 0),
+            core1_data_crc: (0, 0),
+            overlay_names: vec!["a", "b"],
+            overlay_offsets: vec![overlay("a"), overlay("b")],
+            overlay_crcs: vec![((0, 0), (0, 0)); 2],
+            rzip_bytes,
+            stored: Vec::new(),
+            overlay_start_offset: 0x1000,
+            pad_to: Some(MIN_ROM_SIZE),
+            fill: 0xFF,
+            slots: Some(vec![0x1000..0x1100, 0x1100..0x1200]),
+            header_patches: Vec::new(),
+        }
+    }
+
+    //the test roms have no boot code, writing stops at the cic crc with everything else in place
+    fn without_cic<T>(result: Result<T, BkRomError>) {
+        assert!(matches!(result, Err(BkRomError::BadRom(msg)) if msg.contains("cic")));
+    }
+
+    #[test]
+    fn fills_slot_tails() {
+        let uncompressed_rom = vec![0; 0x1000];
+        let symbols = [Symbol{name: String::from("crc_ROM_START"), value: 0x300}];
+        let rom = slotted_rom(&uncompressed_rom, &symbols, vec![vec![1; 0x80], vec![2; 0x40]]);
+        let image = Mutex::new(vec![0; rom.rom_size()]);
+        without_cic(rom.write(&image));
+        let image = image.into_inner().unwrap();
+        assert!(image[0x1000..0x1080].iter().all(|b| *b == 1));
+        assert!(image[0x1080..0x1100].iter().all(|b| *b == 0xFF));
+        assert!(image[0x1100..0x1140].iter().all(|b| *b == 2));
+        assert!(image[0x1140..].iter().all(|b| *b == 0xFF));
+    }
+
+    #[test]
+    fn incremental_matches_full_write() {
+        let uncompressed_rom = vec![0; 0x1000];
+        let symbols = [Symbol{name: String::from("crc_ROM_START"), value: 0x300}];
+        let dir = std::env::temp_dir().join(format!("bkrom_rom_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (full, incremental) = (dir.join("full.z64"), dir.join("incremental.z64"));
+
+        //the second build shrinks both overlays
+        without_cic(slotted_rom(&uncompressed_rom, &symbols, vec![vec![1; 0x100], vec![2; 0x100]]).update_file(&incremental));
+        let shrunk = slotted_rom(&uncompressed_rom, &symbols, vec![vec![3; 0x20], vec![4; 0x10]]);
+        without_cic(shrunk.update_file(&incremental));
+        without_cic(shrunk.write_file(&full));
+        assert!(std::fs::read(&full).unwrap() == std::fs::read(&incremental).unwrap());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+use std::fs;
+use bk_rom_comp::{rzip_uncompressed_size, BkRomError};
+
+#[derive(clap::Args, Debug)]
+pub struct ZipArgs {
+    /// file to compress
+    #[arg(value_name = "FILE")]
+    input: String,
+
+    /// compressed blob, defaults to <FILE>.rzip
+    #[arg(value_name = "OUT")]
+    output: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct UnzipArgs {
+    /// blob to decompress, starting with the 11 72 rarezip header
+    #[arg(value_name = "BLOB")]
+    input: String,
+
+    /// decompressed file, defaults to <BLOB> without .rzip (or with .bin added)
+    #[arg(value_name = "OUT")]
+    output: Option<String>,
+}
+
+pub fn zip(args: ZipArgs) -> Result<(), BkRomError> {
+    let bytes = fs::read(&args.input).map_err(BkRomError::file(&args.input))?;
+    let output = args.output.unwrap_or(format!("{}.rzip", args.input));
+    let rzip = rarezip::bk::zip(&bytes);
+    fs::write(&output, &rzip).map_err(BkRomError::file(&output))?;
+    log::info!("{}: 0x{:X} => 0x{:X} bytes", output, bytes.len(), rzip.len());
+    Ok(())
+}
+
+pub fn unzip(args: UnzipArgs) -> Result<(), BkRomError> {
+    let rzip = fs::read(&args.input).map_err(BkRomError::file(&args.input))?;
+    let size = rzip_uncompressed_size(&rzip).map_err(|e| BkRomError::BadRom(format!("\"{}\": {}", args.input, e)))?;
+    let output = args.output.unwrap_or_else(|| match args.input.strip_suffix(".rzip") {
+        Some(stem) => stem.to_string(),
+        None => format!("{}.bin", args.input),
+    });
+    let bytes = rarezip::bk::unzip(&rzip);
+    if bytes.len() != size {
+        return Err(BkRomError::BadRom(format!("\"{}\" decompressed to 0x{:X} bytes, its header says 0x{:X}", args.input, bytes.len(), size)));
+    }
+    fs::write(&output, &bytes).map_err(BkRomError::file(&output))?;
+    log::info!("{}: 0x{:X} => 0x{:X} bytes", output, rzip.len(), bytes.len());
+    Ok(())
+}
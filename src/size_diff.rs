@@ -0,0 +1,89 @@
+use std::borrow::Cow;
+use crate::comp;
+use bk_rom_comp::{anti_tamper, compress_overlays, profile, read_elf_symbols, store, BkRomError, Overlay, Profile};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// elf of the build to compare against, e.g. the base branch
+    #[arg(long, value_name = "ELF")]
+    old_elf: String,
+
+    /// uncompressed rom (or decompress --split directory) built from --old-elf
+    #[arg(long, value_name = "ROM")]
+    old_rom: String,
+
+    /// elf of the build being reviewed
+    #[arg(long, value_name = "ELF")]
+    new_elf: String,
+
+    /// uncompressed rom (or decompress --split directory) built from --new-elf
+    #[arg(long, value_name = "ROM")]
+    new_rom: String,
+
+    /// sets version of banjo used [default: us.v10]
+    #[arg(short = 'v', long = "game-version", value_name = "VERSION_ID", value_parser = comp::version_names())]
+    game_version: Option<String>,
+
+    /// reads the version's overlays and anti-tamper slots from a TOML game definition
+    #[arg(long, value_name = "FILE", conflicts_with = "game_version")]
+    game_def: Option<String>,
+
+    /// reads the anti-tamper slots from a TOML descriptor instead of the built in
+    /// table of the version (same format as compress --antitamper-config)
+    #[arg(long, value_name = "FILE")]
+    antitamper_config: Option<String>,
+
+    /// reuses compressed overlays from the shared artifact store
+    #[arg(short, long)]
+    cache: bool,
+
+    /// like --cache but keeps the store in <DIR>
+    #[arg(long, value_name = "DIR")]
+    cache_dir: Option<String>,
+
+    /// like --cache but shares the store over http (see compress --cache-url)
+    #[arg(long, value_name = "URL", conflicts_with = "cache_dir")]
+    cache_url: Option<String>,
+}
+
+fn print_size_diff(overlay_names: &[&str], old_rzip_bytes: &[Vec<u8>], new_rzip_bytes: &[Vec<u8>]){
+    println!("{:<10} {:>9} {:>9} {:>8}", "overlay", "old", "new", "delta");
+    for ((name, old), new) in overlay_names.iter().zip(old_rzip_bytes).zip(new_rzip_bytes){
+        println!("{:<10} {:>#9X} {:>#9X} {:>+8}", name, old.len(), new.len(), new.len() as isize - old.len() as isize);
+    }
+    let old_total : usize = old_rzip_bytes.iter().map(Vec::len).sum();
+    let new_total : usize = new_rzip_bytes.iter().map(Vec::len).sum();
+    println!("{:<10} {:>#9X} {:>#9X} {:>+8}", "total", old_total, new_total, new_total as isize - old_total as isize);
+}
+
+//compresses both builds and prints the compressed size change of every overlay, meant
+//for posting on pull requests
+pub fn run(args: Args) -> Result<(), BkRomError> {
+    let profile : Cow<Profile> = match &args.game_def {
+        Some(path) => Cow::Owned(profile::load(path)?),
+        None => Cow::Borrowed(profile::builtin(&comp::game_id(args.game_version.as_deref()))),
+    };
+    let anti_tamper = match &args.antitamper_config {
+        Some(path) => anti_tamper::load(path)?,
+        None => profile.anti_tamper_slots()?.to_vec(),
+    };
+    let store = match (&args.cache_url, &args.cache_dir, args.cache) {
+        (Some(url), _, _) => Some(store::Store::open_http(url)?),
+        (None, Some(dir), _) => Some(store::Store::open(dir)),
+        (None, None, true) => Some(store::Store::open_default().ok_or(BkRomError::NoCacheDir)?),
+        (None, None, false) => None,
+    };
+
+    let overlay_names = profile.overlay_names();
+    let compress = |elf_path: &str, rom_path: &str| -> Result<Vec<Vec<u8>>, BkRomError> {
+        let symbols = read_elf_symbols(elf_path)?;
+        let offsets = overlay_names.iter().map(|name| Overlay::from_elf_symbols(name, &symbols)).collect::<Result<Vec<Overlay>, _>>()?;
+        let mut map = None;
+        let rom = comp::read_uncompressed_rom(rom_path, &offsets, &mut map)?;
+        Ok(compress_overlays(&symbols, &overlay_names, &offsets, &rom, &anti_tamper, store.as_ref(), false)?.rzip_bytes)
+    };
+    let old_rzip_bytes = compress(&args.old_elf, &args.old_rom)?;
+    let new_rzip_bytes = compress(&args.new_elf, &args.new_rom)?;
+    print_size_diff(&overlay_names, &old_rzip_bytes, &new_rzip_bytes);
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//summarizes a directory of compress --report build reports
+use std::fs;
+use bk_rom_comp::BkRomError;
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// directory of build reports (*.json written by compress --report or --out-dir),
+    /// read in file name order, so name them to sort by build, e.g. 0042-1a2b3c.json
+    #[arg(value_name = "DIR")]
+    dir: String,
+
+    /// only flags an overlay when one build grows it by more than this many bytes
+    #[arg(long, value_name = "BYTES", default_value = "0")]
+    threshold: usize,
+}
+
+struct Report {
+    name: String,
+    free: Option<usize>,
+    //(overlay, compressed size)
+    overlays: Vec<(String, usize)>,
+}
+
+//value of a "key": field in one line of a report, reports keep each overlay on its own line
+pub(crate) fn field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let start = line.find(&format!("\"{}\": ", key))? + key.len() + 4;
+    let value = &line[start..];
+    match value.strip_prefix('"') {
+        Some(string) => string.find('"').map(|end| &string[..end]),
+        None => Some(value[..value.find([',', '}']).unwrap_or(value.len())].trim()),
+    }
+}
+
+fn parse_report(name: String, report: &str) -> Report {
+    let number = |line: &str, key: &str| field(line, key).and_then(|v| v.parse().ok());
+    Report {
+        free: report.lines().find_map(|line| line.trim_start().starts_with("\"free\"").then(|| number(line, "free")).flatten()),
+        overlays: report.lines().filter_map(|line| Some((field(line, "name")?.to_string(), number(line, "compressed_size")?))).collect(),
+        name,
+    }
+}
+
+pub fn run(args: Args) -> Result<(), BkRomError> {
+    let mut paths : Vec<std::path::PathBuf> = fs::read_dir(&args.dir).map_err(BkRomError::file(&args.dir))?
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    let mut reports = Vec::new();
+    for path in paths {
+        let report = fs::read_to_string(&path).map_err(BkRomError::file(&path))?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        //manifests and other json in the directory have no compressed sizes
+        match parse_report(name, &report) {
+            report if report.overlays.is_empty() => log::debug!("{}: not a build report, skipping it", path.display()),
+            report => reports.push(report),
+        }
+    }
+    let (Some(first), Some(last)) = (reports.first(), reports.last()) else {
+        return Err(BkRomError::BadRom(format!("\"{}\" has no build reports", args.dir)));
+    };
+
+    //overlays in the order of the latest build, then any that were dropped since
+    let mut overlays : Vec<&str> = last.overlays.iter().map(|(name, _)| name.as_str()).collect();
+    for (name, _) in reports.iter().flat_map(|report| &report.overlays) {
+        if !overlays.contains(&name.as_str()) {
+            overlays.push(name);
+        }
+    }
+    let size = |report: &Report, overlay: &str| report.overlays.iter().find(|(name, _)| name == overlay).map(|(_, size)| *size);
+
+    println!("{} builds, {} to {}", reports.len(), first.name, last.name);
+    println!("{:<10} {:>9} {:>9} {:>9} {:>9}", "overlay", "first", "last", "change", "max");
+    for overlay in &overlays {
+        let sizes : Vec<usize> = reports.iter().filter_map(|report| size(report, overlay)).collect();
+        let (first_size, last_size) = (sizes[0], sizes[sizes.len() - 1]);
+        println!("{:<10} {:>#9X} {:>#9X} {:>+9} {:>#9X}", overlay, first_size, last_size, last_size as isize - first_size as isize, sizes.iter().max().unwrap());
+    }
+    if let (Some(first_free), Some(last_free)) = (first.free, last.free) {
+        println!("{:<10} {:>#9X} {:>#9X} {:>+9}", "free", first_free, last_free, last_free as isize - first_free as isize);
+    }
+
+    //every build that grew an overlay past the threshold, compared to the build before it
+    let mut grown = 0;
+    for pair in reports.windows(2) {
+        for overlay in &overlays {
+            if let (Some(old), Some(new)) = (size(&pair[0], overlay), size(&pair[1], overlay)) {
+                if new > old + args.threshold {
+                    println!("grew: {} {} +{} (0x{:X} => 0x{:X})", pair[1].name, overlay, new - old, old, new);
+                    grown += 1;
+                }
+            }
+        }
+    }
+    if grown == 0 {
+        println!("no overlay grew by more than {} bytes in a single build", args.threshold);
+    }
+    Ok(())
+}
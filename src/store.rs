@@ -0,0 +1,244 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::BkRomError;
+
+//content addressed artifact store shared by every checkout on the machine, or by a whole
+//team through a shared directory or http cache
+//entries are keyed by the md5 of their input, e.g. ~/.cache/bkrom/bk_zip/<md5>, salted
+//with the tool and rarezip versions so an upgrade never reuses another codec's output
+pub struct Store {
+    backend: Backend,
+}
+
+enum Backend {
+    Dir(PathBuf),
+    //GET and PUT <path>/<kind>/<key>, e.g. a bazel-remote or nginx webdav cache
+    //offline is set after the first connection error so a dead cache is only reported once
+    Http{host: String, port: u16, path: String, offline: AtomicBool},
+}
+
+impl Store {
+    pub fn open<P: Into<PathBuf>>(root: P) -> Store {
+        Store { backend: Backend::Dir(root.into()) }
+    }
+
+    //http://host[:port][/path], plain http only, caches are meant to sit on a trusted network
+    pub fn open_http(url: &str) -> Result<Store, BkRomError> {
+        let bad_url = || BkRomError::BadCacheUrl(url.to_string());
+        let rest = url.strip_prefix("http://").ok_or_else(bad_url)?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| bad_url())?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(bad_url());
+        }
+        Ok(Store { backend: Backend::Http{host: host.to_string(), port, path: path.trim_end_matches('/').to_string(), offline: AtomicBool::new(false)} })
+    }
+
+    //$BKROM_CACHE_DIR, then $XDG_CACHE_HOME/bkrom, then ~/.cache/bkrom
+    pub fn open_default() -> Option<Store> {
+        let root = match std::env::var_os("BKROM_CACHE_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => match std::env::var_os("XDG_CACHE_HOME") {
+                Some(dir) => PathBuf::from(dir).join("bkrom"),
+                None => PathBuf::from(std::env::var_os("HOME").or_else(|| std::env::var_os("LOCALAPPDATA"))?).join(".cache").join("bkrom"),
+            },
+        };
+        Some(Store::open(root))
+    }
+
+    fn key(input: &[u8]) -> String {
+        let mut context = md5::Context::new();
+        context.consume(format!("{} {} rarezip {}\n", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), crate::RAREZIP_VERSION));
+        context.consume(input);
+        format!("{:x}", context.compute())
+    }
+
+    //returns the stored artifact for input, producing and storing it if missing
+    //entries that fail valid (e.g. a truncated file or a corrupt upload to a shared cache)
+    //are treated as missing and replaced
+    pub fn get_or_insert_with<V: Fn(&[u8]) -> bool, F: FnOnce() -> Vec<u8>>(&self, kind: &str, input: &[u8], valid: V, f: F) -> Vec<u8> {
+        let key = Store::key(input);
+        let dir = match &self.backend {
+            Backend::Dir(root) => root.join(kind),
+            Backend::Http{offline, ..} if offline.load(Ordering::Relaxed) => return f(),
+            Backend::Http{host, port, path, offline} => {
+                let path = format!("{}/{}/{}", path, kind, key);
+                return match http_get_or_insert_with(host, *port, &path, valid, f) {
+                    Ok(bytes) => bytes,
+                    Err((bytes, e)) => {
+                        if !offline.swap(true, Ordering::Relaxed) {
+                            log::warn!("cache http://{}:{} is unreachable, continuing without it ({})", host, port, e);
+                        }
+                        bytes
+                    },
+                };
+            },
+        };
+        let path = dir.join(&key);
+        match fs::read(&path) {
+            Ok(bytes) if valid(&bytes) => return bytes,
+            Ok(_) => log::warn!("replacing invalid cache entry {}", path.display()),
+            Err(_) => {},
+        }
+
+        let bytes = f();
+        //write to a temp file first so concurrent builds never see a partial entry
+        let tmp = dir.join(format!("{}.{}.tmp", key, std::process::id()));
+        let stored = fs::create_dir_all(&dir)
+            .and_then(|_| fs::write(&tmp, &bytes))
+            .and_then(|_| fs::rename(&tmp, &path));
+        if stored.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        bytes
+    }
+}
+
+//a cache that can't be reached only costs the time to compress again, so the artifact is
+//always produced and a connection error is returned alongside it
+fn http_get_or_insert_with<V: Fn(&[u8]) -> bool, F: FnOnce() -> Vec<u8>>(host: &str, port: u16, path: &str, valid: V, f: F) -> Result<Vec<u8>, (Vec<u8>, std::io::Error)> {
+    match http_request(host, port, "GET", path, &[]) {
+        Ok((200, bytes)) if valid(&bytes) => return Ok(bytes),
+        Ok((200, _)) => log::warn!("replacing invalid cache entry http://{}:{}{}", host, port, path),
+        Ok((status, _)) => log::debug!("cache miss http://{}:{}{} ({})", host, port, path, status),
+        Err(e) => return Err((f(), e)),
+    }
+    let bytes = f();
+    match http_request(host, port, "PUT", path, &bytes) {
+        Ok((200..=299, _)) => Ok(bytes),
+        Ok((status, _)) => {
+            log::warn!("cache http://{}:{}{} refused the upload ({})", host, port, path, status);
+            Ok(bytes)
+        },
+        Err(e) => Err((bytes, e)),
+    }
+}
+
+//one request per connection, returns the status and a body of exactly its Content-Length
+fn http_request(host: &str, port: u16, method: &str, path: &str, body: &[u8]) -> std::io::Result<(u16, Vec<u8>)> {
+    let bad_response = || std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed http response");
+    let mut stream = std::net::TcpStream::connect((host, port))?;
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(30)))?;
+    write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", method, path, host, body.len())?;
+    stream.write_all(body)?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+
+    let header_end = response.windows(4).position(|w| w == b"\r\n\r\n").ok_or_else(bad_response)?;
+    let header = String::from_utf8_lossy(&response[..header_end]);
+    let status = header.split_whitespace().nth(1).and_then(|s| s.parse().ok()).ok_or_else(bad_response)?;
+    let length = header.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("content-length").then(|| value.trim().parse::<usize>().ok()).flatten()
+    });
+    let body = &response[header_end + 4..];
+    match length {
+        Some(length) => Ok((status, body.get(..length).ok_or_else(bad_response)?.to_vec())),
+        //without a length a truncated body can't be told apart, only trust it for errors
+        None if status == 200 => Err(bad_response()),
+        None => Ok((status, body.to_vec())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> (Store, PathBuf) {
+        let root = std::env::temp_dir().join(format!("bkrom-store-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        (Store::open(&root), root)
+    }
+
+    #[test]
+    fn reuses_stored_artifacts() {
+        let (store, root) = temp_store("reuse");
+        assert_eq!(store.get_or_insert_with("bk_zip", b"overlay", |_| true, || b"zipped".to_vec()), b"zipped");
+        assert_eq!(store.get_or_insert_with("bk_zip", b"overlay", |_| true, || unreachable!()), b"zipped");
+        //kinds don't share entries
+        assert_eq!(store.get_or_insert_with("bk_unzip", b"overlay", |_| true, || b"unzipped".to_vec()), b"unzipped");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn ignores_unversioned_entries() {
+        let (store, root) = temp_store("versions");
+        //an entry keyed by the bare input hash, as another tool or codec version might have left it
+        fs::create_dir_all(root.join("bk_zip")).unwrap();
+        fs::write(root.join("bk_zip").join(format!("{:x}", md5::compute(b"overlay"))), b"stale").unwrap();
+        assert_eq!(store.get_or_insert_with("bk_zip", b"overlay", |_| true, || b"zipped".to_vec()), b"zipped");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn replaces_invalid_entries() {
+        let (store, root) = temp_store("invalid");
+        store.get_or_insert_with("bk_zip", b"overlay", |_| true, || b"truncat".to_vec());
+        let valid = |bytes: &[u8]| bytes == b"zipped";
+        assert_eq!(store.get_or_insert_with("bk_zip", b"overlay", valid, || b"zipped".to_vec()), b"zipped");
+        assert_eq!(store.get_or_insert_with("bk_zip", b"overlay", valid, || unreachable!()), b"zipped");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn http_urls() {
+        assert!(Store::open_http("http://cache.local:9090/bkrom/").is_ok());
+        assert!(Store::open_http("http://cache.local").is_ok());
+        assert!(Store::open_http("https://cache.local").is_err());
+        assert!(Store::open_http("http://:80").is_err());
+        assert!(Store::open_http("http://cache.local:port").is_err());
+    }
+
+    //a one shot server answering each request from a map of stored entries
+    #[test]
+    fn http_store() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let mut entries = std::collections::HashMap::<String, Vec<u8>>::new();
+            let mut requests = Vec::new();
+            for stream in listener.incoming().take(5) {
+                let mut stream = stream.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                //read the header, then the body its Content-Length announces
+                let (line, body) = loop {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") else { continue };
+                    let header = String::from_utf8_lossy(&request[..end]).into_owned();
+                    let length : usize = header.lines().find_map(|l| l.strip_prefix("Content-Length: ")).unwrap().parse().unwrap();
+                    if request.len() >= end + 4 + length {
+                        break (header.lines().next().unwrap().to_string(), request[end + 4 ..].to_vec());
+                    }
+                };
+                let mut parts = line.split_whitespace();
+                let (method, path) = (parts.next().unwrap().to_string(), parts.next().unwrap().to_string());
+                let response = match (method.as_str(), entries.get(&path)) {
+                    ("GET", Some(bytes)) => [format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", bytes.len()).into_bytes(), bytes.clone()].concat(),
+                    ("GET", None) => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec(),
+                    _ => {
+                        entries.insert(path.clone(), body);
+                        b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n".to_vec()
+                    },
+                };
+                stream.write_all(&response).unwrap();
+                requests.push(format!("{} {}", method, path));
+            }
+            requests
+        });
+
+        let store = Store::open_http(&format!("http://127.0.0.1:{}/team/", port)).unwrap();
+        assert_eq!(store.get_or_insert_with("bk_zip", b"overlay", |_| true, || b"zipped".to_vec()), b"zipped");
+        assert_eq!(store.get_or_insert_with("bk_zip", b"overlay", |_| true, || unreachable!()), b"zipped");
+        //a corrupt shared entry is replaced rather than used
+        assert_eq!(store.get_or_insert_with("bk_zip", b"overlay", |bytes| bytes == b"rezipped", || b"rezipped".to_vec()), b"rezipped");
+        let path = format!("/team/bk_zip/{}", Store::key(b"overlay"));
+        assert_eq!(server.join().unwrap(), [format!("GET {}", path), format!("PUT {}", path), format!("GET {}", path), format!("GET {}", path), format!("PUT {}", path)]);
+    }
+}
@@ -0,0 +1,299 @@
+//n64 texture formats to and from 8 bit rgba png
+
+use std::io::Write;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TextureFormat {
+    Rgba16,
+    Rgba32,
+    Ci4,
+    Ci8,
+    Ia4,
+    Ia8,
+}
+
+impl TextureFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            TextureFormat::Rgba16 => "rgba16",
+            TextureFormat::Rgba32 => "rgba32",
+            TextureFormat::Ci4 => "ci4",
+            TextureFormat::Ci8 => "ci8",
+            TextureFormat::Ia4 => "ia4",
+            TextureFormat::Ia8 => "ia8",
+        }
+    }
+
+    pub fn bits_per_pixel(self) -> usize {
+        match self {
+            TextureFormat::Ci4 | TextureFormat::Ia4 => 4,
+            TextureFormat::Ci8 | TextureFormat::Ia8 => 8,
+            TextureFormat::Rgba16 => 16,
+            TextureFormat::Rgba32 => 32,
+        }
+    }
+
+    //bytes of the tlut the pixels index into, rgba16 entries
+    pub fn palette_size(self) -> usize {
+        match self {
+            TextureFormat::Ci4 => 16*2,
+            TextureFormat::Ci8 => 256*2,
+            _ => 0,
+        }
+    }
+}
+
+fn rgba16(value: u16) -> [u8; 4] {
+    let channel = |shift: u16| {
+        let c = ((value >> shift) & 0x1F) as u8;
+        (c << 3) | (c >> 2)
+    };
+    [channel(11), channel(6), channel(1), if value & 1 != 0 {0xFF} else {0}]
+}
+
+//one nibble or byte per pixel, high nibble first, an odd count of nibbles still takes a whole last byte
+fn indices(pixels: &[u8], bits: usize, count: usize) -> Result<impl Iterator<Item = u8> + '_, String> {
+    let size = (count*bits).div_ceil(8);
+    if pixels.len() < size {
+        return Err(format!("{} {} bit pixels take 0x{:X} bytes, only 0x{:X} are left", count, bits, size, pixels.len()));
+    }
+    Ok((0..count).map(move |i| match bits {
+        4 => (pixels[i/2] >> if i % 2 == 0 {4} else {0}) & 0xF,
+        _ => pixels[i],
+    }))
+}
+
+pub fn decode(format: TextureFormat, pixels: &[u8], width: usize, height: usize, palette: &[u8]) -> Result<Vec<[u8; 4]>, String> {
+    let count = width*height;
+    let size = (count*format.bits_per_pixel()).div_ceil(8);
+    let pixels = pixels.get(..size).ok_or(format!("a {}x{} {} texture takes 0x{:X} bytes, only 0x{:X} are left", width, height, format.name(), size, pixels.len()))?;
+    let palette = palette.get(..format.palette_size()).ok_or(format!("a {} palette takes 0x{:X} bytes", format.name(), format.palette_size()))?;
+    Ok(match format {
+        TextureFormat::Rgba16 => pixels.chunks_exact(2).map(|p| rgba16(u16::from_be_bytes([p[0], p[1]]))).collect(),
+        TextureFormat::Rgba32 => pixels.chunks_exact(4).map(|p| [p[0], p[1], p[2], p[3]]).collect(),
+        TextureFormat::Ci4 | TextureFormat::Ci8 => indices(pixels, format.bits_per_pixel(), count)?
+            .map(|i| rgba16(u16::from_be_bytes([palette[2*i as usize], palette[2*i as usize + 1]]))).collect(),
+        TextureFormat::Ia4 => indices(pixels, 4, count)?.map(|p| {
+            let i = ((p >> 1) as u16*0xFF/7) as u8;
+            [i, i, i, if p & 1 != 0 {0xFF} else {0}]
+        }).collect(),
+        TextureFormat::Ia8 => pixels.iter().map(|p| {
+            let (i, a) = ((p >> 4)*0x11, (p & 0xF)*0x11);
+            [i, i, i, a]
+        }).collect(),
+    })
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let mut crc = flate2::Crc::new();
+    crc.update(&png[start..]);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+//8 bit rgba, unfiltered
+pub fn to_png(rgba: &[[u8; 4]], width: usize, height: usize) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    for row in rgba.chunks_exact(width).take(height) {
+        encoder.write_all(&[0]).unwrap();
+        row.iter().for_each(|pixel| encoder.write_all(pixel).unwrap());
+    }
+    let pixels = encoder.finish().unwrap();
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &pixels);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {a} else if pb <= pc {b} else {c}
+}
+
+//reads 8 bit grey, grey+alpha, rgb and rgba pngs without interlacing, what image
+//editors save by default, returns the pixels as rgba with the width and height
+pub fn from_png(png: &[u8]) -> Result<(Vec<[u8; 4]>, usize, usize), String> {
+    if png.get(..8) != Some(b"\x89PNG\r\n\x1a\n") {
+        return Err(String::from("not a png"));
+    }
+    let mut header = None;
+    let mut compressed = Vec::new();
+    let mut pos = 8;
+    while let Some(len) = png.get(pos .. pos + 4).map(|l| u32::from_be_bytes(l.try_into().unwrap()) as usize) {
+        let kind = png.get(pos + 4 .. pos + 8).ok_or("truncated png")?;
+        let data = png.get(pos + 8 .. pos + 8 + len).ok_or("truncated png")?;
+        match kind {
+            b"IHDR" => header = Some(data),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {},
+        }
+        pos += 12 + len;
+    }
+    let header = header.filter(|h| h.len() == 13).ok_or("png has no header")?;
+    let width = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap()) as usize;
+    let channels = match (header[8], header[9], header[12]) {
+        (8, 0, 0) => 1,
+        (8, 4, 0) => 2,
+        (8, 2, 0) => 3,
+        (8, 6, 0) => 4,
+        _ => return Err(String::from("only 8 bit grey, grey+alpha, rgb and rgba pngs without interlacing are supported, re-save it as rgba")),
+    };
+
+    let mut raw = Vec::new();
+    std::io::Read::read_to_end(&mut flate2::read::ZlibDecoder::new(compressed.as_slice()), &mut raw).map_err(|e| format!("png data is corrupt ({})", e))?;
+    let stride = width*channels;
+    if raw.len() < height*(stride + 1) {
+        return Err(String::from("png data is truncated"));
+    }
+    let mut pixels = vec![0u8; height*stride];
+    for y in 0..height {
+        let filter = raw[y*(stride + 1)];
+        let line = &raw[y*(stride + 1) + 1 .. (y + 1)*(stride + 1)];
+        for x in 0..stride {
+            let a = if x >= channels {pixels[y*stride + x - channels]} else {0};
+            let b = if y > 0 {pixels[(y - 1)*stride + x]} else {0};
+            let c = if x >= channels && y > 0 {pixels[(y - 1)*stride + x - channels]} else {0};
+            pixels[y*stride + x] = line[x].wrapping_add(match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16)/2) as u8,
+                4 => paeth(a, b, c),
+                f => return Err(format!("png uses unknown filter {}", f)),
+            });
+        }
+    }
+    let rgba = pixels.chunks_exact(channels).map(|p| match p {
+        [i] => [*i, *i, *i, 0xFF],
+        [i, a] => [*i, *i, *i, *a],
+        [r, g, b] => [*r, *g, *b, 0xFF],
+        [r, g, b, a] => [*r, *g, *b, *a],
+        _ => unreachable!(),
+    }).collect();
+    Ok((rgba, width, height))
+}
+
+fn to_rgba16(pixel: [u8; 4]) -> u16 {
+    let [r, g, b, a] = pixel.map(|c| c as u16);
+    (r >> 3) << 11 | (g >> 3) << 6 | (b >> 3) << 1 | (a >= 0x80) as u16
+}
+
+//pixels and (for ci formats) the rgba16 palette, fails if a ci texture has more colors than
+//its palette holds after reducing them to rgba16
+pub fn encode(format: TextureFormat, rgba: &[[u8; 4]]) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let intensity = |p: [u8; 4]| ((p[0] as u16 + p[1] as u16 + p[2] as u16)/3) as u8;
+    let nibbles = |values: Vec<u8>| values.chunks(2).map(|n| n[0] << 4 | n.get(1).copied().unwrap_or(0)).collect::<Vec<u8>>();
+    Ok(match format {
+        TextureFormat::Rgba16 => (rgba.iter().flat_map(|p| to_rgba16(*p).to_be_bytes()).collect(), Vec::new()),
+        TextureFormat::Rgba32 => (rgba.iter().flatten().copied().collect(), Vec::new()),
+        TextureFormat::Ia4 => (nibbles(rgba.iter().map(|p| (intensity(*p) >> 5) << 1 | (p[3] >= 0x80) as u8).collect()), Vec::new()),
+        TextureFormat::Ia8 => (rgba.iter().map(|p| (intensity(*p) >> 4) << 4 | p[3] >> 4).collect(), Vec::new()),
+        TextureFormat::Ci4 | TextureFormat::Ci8 => {
+            let max_colors = format.palette_size()/2;
+            let mut colors : Vec<u16> = Vec::new();
+            let indices : Vec<u8> = rgba.iter().map(|p| {
+                let color = to_rgba16(*p);
+                match colors.iter().position(|c| *c == color) {
+                    Some(i) => i as u8,
+                    None => {
+                        colors.push(color);
+                        (colors.len() - 1) as u8
+                    },
+                }
+            }).collect();
+            if colors.len() > max_colors {
+                return Err(format!("the image has {} colors (as rgba16), a {} palette holds {}", colors.len(), format.name(), max_colors));
+            }
+            let mut palette : Vec<u8> = colors.iter().flat_map(|c| c.to_be_bytes()).collect();
+            palette.resize(format.palette_size(), 0);
+            let pixels = if format == TextureFormat::Ci4 {nibbles(indices)} else {indices};
+            (pixels, palette)
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS : [TextureFormat; 6] = [TextureFormat::Rgba16, TextureFormat::Rgba32, TextureFormat::Ci4, TextureFormat::Ci8, TextureFormat::Ia4, TextureFormat::Ia8];
+
+    //an odd pixel count so the 4 bit formats end on half a byte
+    const WIDTH : usize = 5;
+    const HEIGHT : usize = 3;
+
+    //decoding arbitrary bytes only gives colors the format can hold, so they must survive a round trip
+    fn texture(format: TextureFormat) -> Vec<[u8; 4]> {
+        let pixels : Vec<u8> = (0..WIDTH*HEIGHT*4).map(|i| (i*37 + 11) as u8).collect();
+        let palette : Vec<u8> = (0..format.palette_size()).map(|i| (i*53 + 7) as u8).collect();
+        decode(format, &pixels, WIDTH, HEIGHT, &palette).unwrap()
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for format in FORMATS {
+            let rgba = texture(format);
+            let (pixels, palette) = encode(format, &rgba).unwrap();
+            assert_eq!(pixels.len(), (WIDTH*HEIGHT*format.bits_per_pixel()).div_ceil(8), "{}", format.name());
+            assert_eq!(decode(format, &pixels, WIDTH, HEIGHT, &palette).unwrap(), rgba, "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn png_round_trip() {
+        for format in FORMATS {
+            let rgba = texture(format);
+            assert_eq!(from_png(&to_png(&rgba, WIDTH, HEIGHT)).unwrap(), (rgba, WIDTH, HEIGHT), "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn decode_rejects_short_pixels() {
+        //15 4 bit pixels take 8 bytes, not 7
+        assert!(decode(TextureFormat::Ia4, &[0; 7], WIDTH, HEIGHT, &[]).is_err());
+        assert!(decode(TextureFormat::Ci4, &[0; 7], WIDTH, HEIGHT, &[0; 32]).is_err());
+        assert!(decode(TextureFormat::Ci4, &[0; 8], WIDTH, HEIGHT, &[0; 31]).is_err());
+    }
+
+    #[test]
+    fn encode_rejects_too_many_colors() {
+        let rgba : Vec<[u8; 4]> = (0..17u8).map(|i| [i << 3, 0, 0, 0xFF]).collect();
+        assert!(encode(TextureFormat::Ci4, &rgba).is_err());
+        assert!(encode(TextureFormat::Ci8, &rgba).is_ok());
+    }
+
+    //3x4 rgba with a text chunk and one row per sub, up, average and paeth filter
+    const FILTERED_PNG : &[u8] = b"\x89\x50\x4e\x47\x0d\x0a\x1a\x0a\x00\x00\x00\x0d\x49\x48\x44\x52\x00\x00\x00\x03\x00\x00\x00\x04\
+        \x08\x06\x00\x00\x00\x4b\x2d\x85\x07\x00\x00\x00\x0d\x74\x45\x58\x74\x43\x6f\x6d\x6d\x65\x6e\x74\
+        \x00\x62\x6b\x72\x6f\x6d\x9f\x0b\x7d\x23\x00\x00\x00\x32\x49\x44\x41\x54\x78\xda\x63\x64\x38\xc1\
+        \xd0\xc0\xf5\x86\xb1\x1e\x88\x1b\x99\x34\x18\xe4\xea\x81\xb8\x11\x44\x33\xdb\xa4\xe8\x32\x4a\x7e\
+        \x13\xa8\x07\xe2\x46\x16\x90\x08\x17\x03\x63\x23\x10\xd7\x03\x00\x5f\xac\x0c\x79\xcf\x8a\xce\xe8\
+        \x00\x00\x00\x00\x49\x45\x4e\x44\xae\x42\x60\x82";
+
+    #[test]
+    fn decode_filtered_png() {
+        let expected : Vec<[u8; 4]> = (0..4).flat_map(|y| (0..3).map(move |x| {
+            [10*x + 40*y, 200 - 20*x, 30*y + x, if (x + y) % 2 != 0 {255} else {128}]
+        })).collect();
+        assert_eq!(from_png(FILTERED_PNG).unwrap(), (expected, 3, 4));
+    }
+
+    #[test]
+    fn from_png_rejects_garbage() {
+        assert!(from_png(b"not a png").is_err());
+        assert!(from_png(&FILTERED_PNG[..40]).is_err());
+    }
+}
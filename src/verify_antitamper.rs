@@ -0,0 +1,61 @@
+use std::borrow::Cow;
+use bk_rom_comp::{anti_tamper, map_rom, as_big_endian, check_anti_tamper, decompress_rom, profile, read_elf_symbols, BkRomError, GameId, Profile};
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// compressed rom to check, in any byte order
+    #[arg(value_name = "ROM")]
+    path: String,
+
+    /// elf the rom was built from, it locates the anti-tamper slots and overlays
+    #[arg(value_name = "ELF")]
+    elf_path: String,
+
+    /// reads the anti-tamper slots from a TOML descriptor instead of the built in
+    /// table of the version (same format as compress --antitamper-config)
+    #[arg(long, value_name = "FILE")]
+    antitamper_config: Option<String>,
+
+    /// reads the version's overlays and anti-tamper slots from a TOML game definition
+    #[arg(long, value_name = "FILE")]
+    game_def: Option<String>,
+}
+
+pub fn run(args: Args) -> Result<(), BkRomError> {
+    let source = map_rom(&args.path)?;
+    let rom = as_big_endian(&source)?;
+    let symbols = read_elf_symbols(&args.elf_path)?;
+
+    let profile : Cow<Profile> = match &args.game_def {
+        Some(path) => Cow::Owned(profile::load(path)?),
+        None => {
+            let md5 = format!("{:x}", md5::compute(&rom[..]));
+            match profile::PROFILES.iter().find(|p| p.compressed_md5.as_deref() == Some(md5.as_str())) {
+                Some(profile) => Cow::Borrowed(profile),
+                None => {
+                    let game_id = GameId::from_header(&rom).ok_or_else(|| BkRomError::UnsupportedVersion(format!("\"{}\" has an unknown md5 {} and header", args.path, md5)))?;
+                    Cow::Borrowed(profile::builtin(&game_id))
+                },
+            }
+        },
+    };
+    let slots = match &args.antitamper_config {
+        Some(path) => anti_tamper::load(path)?,
+        None => profile.anti_tamper_slots()?.to_vec(),
+    };
+
+    let decompressed = decompress_rom(&rom, &profile)?;
+    let checks = check_anti_tamper(&decompressed, &symbols, &profile, &slots)?;
+    let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    for check in &checks {
+        let status = if check.stored == check.expected {"ok"} else {"MISMATCH"};
+        println!("{:<width$}  stored 0x{:08X}  expected 0x{:08X}  {}", check.name, check.stored, check.expected, status, width = width);
+    }
+
+    let mismatches = checks.iter().filter(|c| c.stored != c.expected).count();
+    if mismatches > 0 {
+        return Err(BkRomError::BadRom(format!("{} of {} anti-tamper values don't match, \"{}\" will crash once the game checks them", mismatches, checks.len(), args.path)));
+    }
+    log::info!("all {} anti-tamper values match", checks.len());
+    Ok(())
+}
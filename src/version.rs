@@ -0,0 +1,31 @@
+//tool identification for reproducibility audits
+use bk_rom_comp::{profile, BkRomError, RAREZIP_VERSION};
+
+pub const TOOL_VERSION : &str = env!("CARGO_PKG_VERSION");
+
+pub fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "serde") {
+        features.push("serde");
+    }
+    if cfg!(feature = "upload") {
+        features.push("upload");
+    }
+    features
+}
+
+//e.g. "bk_rom_comp 0.1.0 (rarezip 0.1.0, profiles 1a2b3c4d, features: serde)"
+pub fn describe() -> String {
+    format!("{} {} (rarezip {}, profiles {}, features: {})", env!("CARGO_PKG_NAME"), TOOL_VERSION, RAREZIP_VERSION, profile::revision(), match enabled_features().join(", ") {
+        f if f.is_empty() => String::from("none"),
+        f => f,
+    })
+}
+
+//builds that need to match must come from exactly the pinned version
+pub fn require(version: &str) -> Result<(), BkRomError> {
+    match version == TOOL_VERSION {
+        true => Ok(()),
+        false => Err(BkRomError::ToolVersion{required: version.to_string(), actual: describe()}),
+    }
+}